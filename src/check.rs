@@ -0,0 +1,401 @@
+use std::{collections::HashMap, fs::read_to_string, path::Path};
+
+use crate::{
+    GAMEPAD_KEYS, chord,
+    config::{BASE_LAYER, Config, Window},
+    gestures::split_gesture,
+    keys::{modifier_key, named_key},
+    layers::LayerAction,
+    lexer::{Diagnostic, Span},
+    macros::parse_sequence,
+    parser::{Component, Declaration, Rule},
+    repeat::{parse_repeat, parse_turbo},
+    settings::Settings,
+    simulation::{MOUSE_ACTIONS, hold_keys, parse_hold},
+    sticks::STICK_BUTTONS,
+    toggles::{check_toggle, parse_toggle},
+};
+
+fn is_plugin_rule(rule: &Rule) -> bool {
+    rule.selectors
+        .iter()
+        .all(|sel| matches!(sel.components.first(), Some(Component::Id(id)) if id == "plugin"))
+}
+
+fn check_binding(
+    decl: &Declaration,
+    src: &str,
+    plugins_dir: &str,
+    layers: &[&str],
+    out: &mut Vec<Diagnostic>,
+) {
+    let (chord, gesture) = split_gesture(&decl.name);
+    if let Some(Err(e)) = gesture {
+        out.push(Diagnostic::new(src, decl.span, e));
+    }
+
+    if chord::mixes_orders(chord) {
+        out.push(Diagnostic::new(
+            src,
+            decl.span,
+            format!(
+                "`{}` mixes `-` and `+`, a chord is either ordered or not",
+                chord
+            ),
+        ));
+    }
+
+    for button in chord::buttons(chord) {
+        if !GAMEPAD_KEYS.contains(&button) && !STICK_BUTTONS.contains(&button) {
+            out.push(Diagnostic::new(
+                src,
+                decl.span,
+                format!("unknown button `{}` in `{}`", button, decl.name),
+            ));
+        }
+    }
+
+    if let Some(plugin) = decl.value.strip_prefix('@') {
+        let binname = plugin.split('+').next().unwrap_or_default();
+        if !Path::new(plugins_dir).join(binname).exists() {
+            out.push(Diagnostic::new(
+                src,
+                decl.span,
+                format!("plugin `@{}` not found in {}", binname, plugins_dir),
+            ));
+        }
+        return;
+    }
+
+    if let Some(action) = LayerAction::parse(&decl.value) {
+        let layer = action.layer();
+        // `to-layer(base)` is the one way to name the bindings outside every layer
+        let is_base = layer == BASE_LAYER && matches!(action, LayerAction::To(_));
+        if !is_base && !layers.contains(&layer) {
+            out.push(Diagnostic::new(
+                src,
+                decl.span,
+                format!("no `@layer {}` is declared", layer),
+            ));
+        }
+        return;
+    }
+
+    if let Some(combo) = parse_hold(&decl.value) {
+        if let Err(e) = hold_keys(combo) {
+            out.push(Diagnostic::new(src, decl.span, e));
+        }
+        return;
+    }
+
+    if let Some(action) = parse_toggle(&decl.value) {
+        if let Err(e) = check_toggle(action) {
+            out.push(Diagnostic::new(src, decl.span, e));
+        }
+        return;
+    }
+
+    match parse_sequence(&decl.value) {
+        Some(Ok(_)) => return,
+        Some(Err(e)) => {
+            out.push(Diagnostic::new(src, decl.span, e));
+            return;
+        }
+        None => {}
+    }
+
+    let repeat = parse_repeat(&decl.value).or_else(|| parse_turbo(&decl.value));
+    let combo = match repeat {
+        Some(Ok(repeat)) => repeat.action,
+        Some(Err(e)) => {
+            out.push(Diagnostic::new(src, decl.span, e));
+            return;
+        }
+        None => decl.value.as_str(),
+    };
+
+    for token in combo.split('+') {
+        let name = token.to_lowercase();
+        if modifier_key(&name).is_some()
+            || named_key(&name).is_some()
+            || MOUSE_ACTIONS.contains(&name.as_str())
+            || token.chars().count() <= 1
+        {
+            continue;
+        }
+
+        out.push(Diagnostic::new(
+            src,
+            decl.span,
+            format!(
+                "`{}` is not a key name and would be typed as literal text",
+                token
+            ),
+        ));
+    }
+}
+
+/// Static checks that need nothing but the parsed config and the plugins directory.
+pub fn lint(cfg: &Config, src: &str, plugins_dir: &str) -> Vec<Diagnostic> {
+    let mut out = vec![];
+
+    let mut first_seen: HashMap<String, Span> = HashMap::new();
+    let mut effective: HashMap<String, HashMap<&str, &Declaration>> = HashMap::new();
+    let layers = cfg.layers();
+
+    for rule in &cfg.rules {
+        for sel in &rule.selectors {
+            let key = match &rule.layer {
+                Some(layer) => format!("@layer {} {}", layer, sel),
+                None => sel.to_string(),
+            };
+            match first_seen.get(&key) {
+                // runs of bare declarations in a layer are one block as written
+                _ if rule.bare => {}
+                Some(first) => out.push(Diagnostic::new(
+                    src,
+                    sel.span,
+                    format!(
+                        "duplicate selector `{}`, first declared at {}:{}",
+                        key, first.line, first.column
+                    ),
+                )),
+                None => {
+                    first_seen.insert(key.clone(), sel.span);
+                }
+            }
+
+            let bindings = effective.entry(key).or_default();
+            for decl in &rule.declarations {
+                let Some(prev) = bindings.get(decl.name.as_str()).copied() else {
+                    bindings.insert(&decl.name, decl);
+                    continue;
+                };
+                if std::ptr::eq(prev, decl) {
+                    continue;
+                }
+
+                // an earlier `!important` survives a later plain declaration
+                let (loser, winner) = if prev.important && !decl.important {
+                    (decl, prev)
+                } else {
+                    bindings.insert(&decl.name, decl);
+                    (prev, decl)
+                };
+
+                out.push(Diagnostic::new(
+                    src,
+                    loser.span,
+                    format!(
+                        "`{}` is overridden at {}:{} and never fires",
+                        loser.name, winner.span.line, winner.span.column
+                    ),
+                ));
+            }
+        }
+
+        if is_plugin_rule(rule) {
+            continue;
+        }
+
+        for decl in &rule.declarations {
+            if decl.name.starts_with("--") {
+                if let Err(e) = Settings::validate(&decl.name, &decl.value) {
+                    out.push(Diagnostic::new(src, decl.span, e));
+                }
+                continue;
+            }
+            check_binding(decl, src, plugins_dir, &layers, &mut out);
+        }
+    }
+
+    // bindings in `*` apply everywhere else too, unless overridden
+    let universal = effective.get("*").cloned().unwrap_or_default();
+    let mut reported: Vec<usize> = vec![];
+    for (key, bindings) in &effective {
+        let mut visible = match key.as_str() {
+            "*" => HashMap::new(),
+            _ => universal.clone(),
+        };
+        visible.extend(bindings.iter().map(|(name, decl)| (*name, *decl)));
+
+        for decl in bindings.values() {
+            if reported.contains(&decl.span.offset) {
+                continue;
+            }
+            if let Some((direction, holder)) = blocked_by_mouse(&decl.name, &visible) {
+                reported.push(decl.span.offset);
+                out.push(Diagnostic::new(
+                    src,
+                    decl.span,
+                    format!(
+                        "`{}` never fires, `{}` isn't a button while `{}` holds `mouse`",
+                        decl.name, direction, holder
+                    ),
+                ));
+            }
+        }
+    }
+
+    out.sort_by_key(|d| d.span.offset);
+    out
+}
+
+/// The stick direction in an ordered chord that can't be pressed, because an earlier part of
+/// the chord is bound to `mouse` and sticks aren't buttons in mouse mode, along with that part.
+/// Mouse mode lasts from a `mouse` binding until a part of the chord that has no binding.
+fn blocked_by_mouse<'a>(
+    name: &'a str,
+    bindings: &HashMap<&str, &Declaration>,
+) -> Option<(&'a str, String)> {
+    let (chord, _) = split_gesture(name);
+    // `+` chords can be pressed with the directions first
+    if chord.contains('+') {
+        return None;
+    }
+
+    let buttons = chord::buttons(chord);
+    let mut holder: Option<String> = None;
+    for i in 0..buttons.len() {
+        if let Some(holder) = &holder
+            && STICK_BUTTONS.contains(&buttons[i])
+        {
+            return Some((buttons[i], holder.clone()));
+        }
+
+        let prefix = buttons[..=i].join("-");
+        match bindings.get(prefix.as_str()).map(|d| d.value.as_str()) {
+            Some("mouse") => holder = Some(prefix),
+            None => holder = None,
+            Some(_) => {}
+        }
+    }
+    None
+}
+
+/// Validates a config file and reports every problem found, returns whether it was clean.
+pub fn check(config_path: &str, plugins_dir: &str) -> bool {
+    let src = match read_to_string(config_path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("error reading {}: {}", config_path, e);
+            return false;
+        }
+    };
+
+    let cfg = match Config::load_from(&src) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("{}", e.in_file(config_path));
+            return false;
+        }
+    };
+
+    let problems = lint(&cfg, &src, plugins_dir);
+    if problems.is_empty() {
+        println!("{}: ok", config_path);
+        return true;
+    }
+
+    let count = problems.len();
+    for p in problems {
+        eprintln!("{}\n", p.in_file(config_path));
+    }
+    eprintln!("{}: {} problem(s) found", config_path, count);
+
+    false
+}
+
+/// Prints every binding that applies to a window and the rule it came from.
+pub fn explain(config_path: &str, window: &Window) -> bool {
+    let src = match read_to_string(config_path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("error reading {}: {}", config_path, e);
+            return false;
+        }
+    };
+
+    let cfg = match Config::load_from(&src) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("{}", e.in_file(config_path));
+            return false;
+        }
+    };
+
+    let layers = cfg.layers();
+    for layer in std::iter::once(None).chain(layers.into_iter().map(Some)) {
+        let applied = cfg.explain(window, layer);
+        let indent = match layer {
+            Some(_) if applied.is_empty() => continue,
+            Some(layer) => {
+                println!("\n@layer {}, when on:", layer);
+                "  "
+            }
+            None => "",
+        };
+
+        for a in applied {
+            let important = if a.important { " !important" } else { "" };
+            println!(
+                "{}{}: {:?}{}  from `{}` at {}:{}:{} (specificity {},{})",
+                indent,
+                a.name,
+                a.value,
+                important,
+                a.selector,
+                config_path,
+                a.span.line,
+                a.span.column,
+                a.specificity.0,
+                a.specificity.1
+            );
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(src: &str) -> Vec<String> {
+        let cfg = Config::load_from(src).unwrap();
+        lint(&cfg, src, "plugins")
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn stick_directions_under_a_mouse_hold_are_unreachable() {
+        let src = r#"
+* {
+  rb: "mouse";
+  rb-a: "click";
+  rb-ls-up: "ctrl+c";
+  rb+ls-down: "ctrl+v";
+  lb-ls-up: "x";
+}
+.app {
+  rb-rs-left: "y";
+  rb-b-rs-left: "z";
+}
+"#;
+        assert_eq!(
+            messages(src),
+            [
+                "`rb-ls-up` never fires, `ls-up` isn't a button while `rb` holds `mouse`",
+                "`rb-rs-left` never fires, `rs-left` isn't a button while `rb` holds `mouse`",
+            ]
+        );
+    }
+
+    #[test]
+    fn overrides_are_reported_once() {
+        let src = r#"* { a: "x"; a: "mouse"; }"#;
+        assert_eq!(messages(src), ["`a` is overridden at 1:13 and never fires"]);
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use crate::{
+    GAMEPAD_KEYS,
+    gestures::{has_gestures, split_gesture},
+    settings::ChordOrder,
+    sticks::STICK_BUTTONS,
+};
+
+/// Splits a chord into its buttons, each with the separator before it. Stick directions like
+/// `ls-down-left` have `-` in their name, so the longest one that fits is a single button.
+fn split(chord: &str) -> Vec<(Option<char>, &str)> {
+    let mut parts = vec![];
+    let mut sep = None;
+    let mut rest = chord;
+
+    loop {
+        let len = STICK_BUTTONS
+            .iter()
+            .filter(|b| {
+                rest.starts_with(**b)
+                    && matches!(rest[b.len()..].chars().next(), None | Some('-' | '+'))
+            })
+            .map(|b| b.len())
+            .max()
+            .unwrap_or_else(|| rest.find(['-', '+']).unwrap_or(rest.len()));
+        parts.push((sep, &rest[..len]));
+
+        let mut tail = rest[len..].chars();
+        match tail.next() {
+            Some(c) => {
+                sep = Some(c);
+                rest = tail.as_str();
+            }
+            None => return parts,
+        }
+    }
+}
+
+/// The buttons of a chord name, `rb-lt` and `rb+lt` both give `rb`, `lt`.
+pub fn buttons(chord: &str) -> Vec<&str> {
+    split(chord).into_iter().map(|(_, b)| b).collect()
+}
+
+/// Whether `chord` has both `-` and `+` between its buttons.
+pub fn mixes_orders(chord: &str) -> bool {
+    let parts = split(chord);
+    parts.iter().any(|(s, _)| *s == Some('-')) && parts.iter().any(|(s, _)| *s == Some('+'))
+}
+
+/// `rb+lt` can be pressed in any order, so it's stored with its buttons in `GAMEPAD_KEYS` order
+/// and `lt+rb` ends up as the same binding. Ordered chords and gesture suffixes are left as-is.
+pub fn canonical(name: &str) -> String {
+    let (chord, _) = split_gesture(name);
+    if !split(chord).iter().any(|(s, _)| *s == Some('+')) {
+        return name.to_string();
+    }
+
+    let order = || GAMEPAD_KEYS.iter().chain(&STICK_BUTTONS);
+    let mut parts = buttons(chord);
+    parts.sort_by_key(|b| (order().position(|k| k == b), *b));
+    format!("{}{}", parts.join("+"), &name[chord.len()..])
+}
+
+fn is_bound(chord: &str, bindings: &HashMap<String, String>) -> bool {
+    bindings.contains_key(chord) || has_gestures(chord, bindings)
+}
+
+fn same_buttons(chord: &str, held: &[String]) -> bool {
+    let mut a = buttons(chord);
+    let mut b: Vec<&str> = held.iter().map(String::as_str).collect();
+    a.sort();
+    b.sort();
+    a == b
+}
+
+/// The binding name for `held`, in press order. An ordered chord like `rb-lt` wins over an
+/// unordered `rb+lt`, and with `--chord-order: any` an ordered chord also matches in any order.
+/// Falls back to the ordered name when nothing is bound.
+pub fn resolve(held: &[String], bindings: &HashMap<String, String>, order: ChordOrder) -> String {
+    // `ls` then `up` joins to `ls-up`, which is the stick direction and not this chord
+    let ordered = Some(held.join("-")).filter(|o| buttons(o) == held);
+    let unordered = canonical(&held.join("+"));
+
+    if let Some(ordered) = &ordered
+        && (held.len() < 2 || is_bound(ordered, bindings))
+    {
+        return ordered.clone();
+    }
+    if is_bound(&unordered, bindings) {
+        return unordered;
+    }
+
+    if order == ChordOrder::Any {
+        let found = bindings
+            .keys()
+            .filter(|name| !name.starts_with("--"))
+            .map(|name| split_gesture(name).0)
+            .filter(|chord| split(chord).iter().any(|(s, _)| *s == Some('-')))
+            .filter(|chord| same_buttons(chord, held))
+            .min();
+        if let Some(chord) = found {
+            return chord.to_string();
+        }
+    }
+
+    ordered.unwrap_or(unordered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn any_order_chords_have_one_spelling() {
+        assert_eq!(canonical("rb+lt"), "lt+rb");
+        assert_eq!(canonical("lt+rb"), "lt+rb");
+        assert_eq!(canonical("rb+a+lb"), "a+lb+rb");
+        assert_eq!(canonical("rb+a:hold"), "a+rb:hold");
+        assert_eq!(canonical("rs-down-left+ls-up+y"), "y+ls-up+rs-down-left");
+    }
+
+    #[test]
+    fn ordered_chords_keep_their_order() {
+        assert_eq!(canonical("rb-a"), "rb-a");
+        assert_eq!(canonical("ls-up-left"), "ls-up-left");
+        assert_eq!(canonical("rb-lt:double"), "rb-lt:double");
+    }
+
+    #[test]
+    fn stick_directions_are_single_buttons() {
+        assert_eq!(buttons("ls-up-left-a"), ["ls-up-left", "a"]);
+        assert_eq!(buttons("rb+ls-down"), ["rb", "ls-down"]);
+        assert!(mixes_orders("rb-lt+a"));
+        assert!(!mixes_orders("rb-ls-up"));
+    }
+
+    #[test]
+    fn ordered_chords_win_over_any_order_ones() {
+        let held = ["rb".to_string(), "lt".to_string()];
+        let bindings = |names: &[&str]| -> HashMap<String, String> {
+            names
+                .iter()
+                .map(|n| (n.to_string(), String::new()))
+                .collect()
+        };
+
+        let unordered = bindings(&["lt+rb"]);
+        assert_eq!(resolve(&held, &unordered, ChordOrder::Pressed), "lt+rb");
+
+        let both = bindings(&["lt+rb", "rb-lt"]);
+        assert_eq!(resolve(&held, &both, ChordOrder::Pressed), "rb-lt");
+
+        let reversed = bindings(&["lt-rb"]);
+        assert_eq!(resolve(&held, &reversed, ChordOrder::Pressed), "rb-lt");
+        assert_eq!(resolve(&held, &reversed, ChordOrder::Any), "lt-rb");
+    }
+}
@@ -0,0 +1,124 @@
+use std::{env, path::PathBuf};
+
+use crate::{config::Window, logging::Level, sink::Backend};
+
+pub const USAGE: &str = "\
+usage: orchidbox [options]
+       orchidbox [options] check
+       orchidbox [options] explain <class> [title] [process]
+       orchidbox keys
+
+options:
+  --config <path>       config file, defaults to ./config.css,
+                        then $XDG_CONFIG_HOME/orchidbox/config.css
+  --plugins-dir <path>  plugin directory, defaults to ./plugins,
+                        then $XDG_DATA_HOME/orchidbox/plugins
+  --log-level <level>   error, warn, info (default) or debug
+  --backend <name>      how input is sent: auto (default), rdev or uinput
+  --dry-run             log input events instead of sending them
+  -h, --help            print this message";
+
+pub enum Command {
+    Run,
+    Check,
+    Explain(Window),
+    Keys,
+    Help,
+}
+
+pub struct Options {
+    pub command: Command,
+    pub config: String,
+    pub plugins_dir: String,
+    pub log_level: Level,
+    pub backend: Backend,
+    pub dry_run: bool,
+}
+
+/// `$XDG_<var>/orchidbox/<name>`, or `~/<fallback>/orchidbox/<name>` when the variable is unset.
+fn xdg_path(var: &str, fallback: &str, name: &str) -> Option<PathBuf> {
+    let base = match env::var_os(var) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(fallback),
+    };
+
+    Some(base.join("orchidbox").join(name))
+}
+
+/// Prefers `./<name>` so running from the repo root keeps working.
+fn default_path(var: &str, fallback: &str, name: &str) -> String {
+    let local = PathBuf::from(".").join(name);
+    if local.exists() {
+        return local.to_string_lossy().to_string();
+    }
+
+    xdg_path(var, fallback, name)
+        .unwrap_or(local)
+        .to_string_lossy()
+        .to_string()
+}
+
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut config = None;
+    let mut plugins_dir = None;
+    let mut log_level = Level::Info;
+    let mut backend = Backend::Auto;
+    let mut dry_run = false;
+    let mut positional = vec![];
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // accept both `--flag value` and `--flag=value`
+        let (flag, inline) = match arg.split_once('=') {
+            Some((f, v)) if f.starts_with("--") => (f.to_string(), Some(v.to_string())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("missing value for {}", flag))
+        };
+
+        match flag.as_str() {
+            "--config" => config = Some(value()?),
+            "--plugins-dir" => plugins_dir = Some(value()?),
+            "--log-level" => {
+                let v = value()?;
+                log_level = Level::parse(&v).ok_or_else(|| format!("unknown log level `{}`", v))?;
+            }
+            "--backend" => {
+                let v = value()?;
+                backend = Backend::parse(&v).ok_or_else(|| format!("unknown backend `{}`", v))?;
+            }
+            "--dry-run" => dry_run = true,
+            "-h" | "--help" => positional.insert(0, "help".to_string()),
+            f if f.starts_with('-') => return Err(format!("unknown option `{}`", f)),
+            _ => positional.push(arg),
+        }
+    }
+
+    let arg = |i: usize| positional.get(i).cloned().unwrap_or_default();
+    let command = match positional.first().map(String::as_str) {
+        None => Command::Run,
+        Some("help") => Command::Help,
+        Some("check") => Command::Check,
+        Some("keys") => Command::Keys,
+        Some("explain") => Command::Explain(Window {
+            class: arg(1),
+            title: arg(2),
+            process: arg(3),
+        }),
+        Some(other) => return Err(format!("unknown command `{}`", other)),
+    };
+
+    Ok(Options {
+        command,
+        config: config.unwrap_or_else(|| default_path("XDG_CONFIG_HOME", ".config", "config.css")),
+        plugins_dir: plugins_dir
+            .unwrap_or_else(|| default_path("XDG_DATA_HOME", ".local/share", "plugins")),
+        log_level,
+        backend,
+        dry_run,
+    })
+}
@@ -0,0 +1,51 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Programs that put their stdin on the clipboard, tried in order.
+#[cfg(target_os = "linux")]
+const COPY_COMMANDS: &[&[&str]] = &[
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard"],
+    &["xsel", "--clipboard", "--input"],
+];
+#[cfg(target_os = "macos")]
+const COPY_COMMANDS: &[&[&str]] = &[&["pbcopy"]];
+#[cfg(target_os = "windows")]
+const COPY_COMMANDS: &[&[&str]] = &[&["clip"]];
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+const COPY_COMMANDS: &[&[&str]] = &[];
+
+fn run(command: &[&str], text: &str) -> bool {
+    let Ok(mut child) = Command::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+    else {
+        return false;
+    };
+
+    // stdin is closed when it's dropped, which is what tells the program the text is over
+    let written = child
+        .stdin
+        .take()
+        .is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+    let exited = child.wait().is_ok_and(|status| status.success());
+    written && exited
+}
+
+/// Puts `text` on the clipboard with the first copy program that works.
+pub fn copy(text: &str) -> Result<(), String> {
+    if COPY_COMMANDS.iter().any(|command| run(command, text)) {
+        return Ok(());
+    }
+
+    let tried: Vec<&str> = COPY_COMMANDS.iter().map(|command| command[0]).collect();
+    Err(format!(
+        "no clipboard program worked, tried: {}",
+        tried.join(", ")
+    ))
+}
@@ -1,167 +1,327 @@
-use std::{collections::HashMap, rc::Rc};
+use std::collections::HashMap;
+
+use crate::{
+    chord,
+    lexer::{Diagnostic, Span},
+    parser::{Rule, Selector, Specificity, parse},
+};
+
+/// What `to-layer(base)` switches to, the bindings outside every layer.
+pub const BASE_LAYER: &str = "base";
 
 pub struct Config {
-    pub stylesheet: HashMap<String, HashMap<String, String>>,
-    keys: Rc<[String]>,
+    pub rules: Vec<Rule>,
 }
 
-impl Config {
-    pub fn load_from(css: &str) -> Self {
-        let mut stylesheet: HashMap<String, HashMap<String, String>> = HashMap::new();
+/// What a selector gets matched against.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Window {
+    pub class: String,
+    pub title: String,
+    /// foreground process of a terminal, only looked up when a selector needs it
+    pub process: String,
+}
 
-        let mut scope_type: u8 = 0; // 0 = outside, 1 = making key, 2 = making value
-        let mut sel_acc = "".to_string();
-        let mut key_acc = "".to_string();
-        let mut val_acc = "".to_string();
+impl Window {
+    pub const ATTRIBUTES: [&str; 3] = ["class", "title", "process"];
 
-        let mut vec_keys = Vec::new();
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        match name {
+            "class" => Some(&self.class),
+            "title" => Some(&self.title),
+            "process" => Some(&self.process),
+            _ => None,
+        }
+    }
+}
 
-        let ignores = [' ', '\t', '\n', '\r', '"'];
+/// A declaration that won the cascade, and where it came from.
+#[derive(Debug, Clone)]
+pub struct Applied<'a> {
+    pub name: &'a str,
+    pub value: &'a str,
+    pub important: bool,
+    pub specificity: Specificity,
+    pub selector: &'a Selector,
+    pub span: Span,
+}
 
-        let chars: Vec<char> = css.chars().collect();
+fn matches_window(sel: &Selector, window: &Window) -> bool {
+    sel.id().is_none()
+        && sel.class_name().is_none_or(|class| class == window.class)
+        && sel
+            .attributes()
+            .all(|a| window.attribute(&a.name).is_some_and(|v| a.matches(v)))
+}
 
-        let mut is_comment = false;
-        for i in 0..chars.len() {
-            let c = chars[i];
+impl Config {
+    pub fn load_from(css: &str) -> Result<Self, Diagnostic> {
+        let mut rules = parse(css)?.rules;
 
-            // handle comments
-            if c == '/' {
-                if chars[i + 1] == '*' {
-                    is_comment = true;
-                    continue;
-                }
-                if chars[i - 1] == '*' {
-                    is_comment = false;
-                    continue;
+        for sel in rules.iter().flat_map(|r| &r.selectors) {
+            if sel.id().is_some() {
+                continue;
+            }
+            for a in sel.attributes() {
+                if !Window::ATTRIBUTES.contains(&a.name.as_str()) {
+                    return Err(Diagnostic::new(
+                        css,
+                        a.span,
+                        format!(
+                            "unknown attribute `{}`, expected one of: {}",
+                            a.name,
+                            Window::ATTRIBUTES.join(", ")
+                        ),
+                    ));
                 }
             }
+        }
+
+        // `to-layer(base)` means no layer at all
+        if let Some(rule) = rules
+            .iter()
+            .find(|r| r.layer.as_deref() == Some(BASE_LAYER))
+        {
+            return Err(Diagnostic::new(
+                css,
+                rule.selectors[0].span,
+                "`base` is the name for the bindings outside any layer and can't be declared",
+            ));
+        }
 
-            if is_comment {
+        // every spelling of an unordered chord is one binding, so it cascades as one
+        for rule in &mut rules {
+            if rule.selectors.iter().any(|sel| sel.id().is_some()) {
                 continue;
             }
+            for decl in &mut rule.declarations {
+                decl.name = chord::canonical(&decl.name);
+            }
+        }
 
-            let mut to_ignore = false;
+        Ok(Self { rules })
+    }
 
-            for i in 0..ignores.len() {
-                if c == ignores[i] {
-                    to_ignore = true;
-                }
+    /// Every `@layer`, in the order they stack, which is the order they're first declared.
+    pub fn layers(&self) -> Vec<&str> {
+        let mut layers: Vec<&str> = vec![];
+        for layer in self.rules.iter().filter_map(|r| r.layer.as_deref()) {
+            if !layers.contains(&layer) {
+                layers.push(layer);
             }
+        }
+        layers
+    }
+
+    pub fn uses_attribute(&self, name: &str) -> bool {
+        self.rules
+            .iter()
+            .flat_map(|r| &r.selectors)
+            .flat_map(|sel| sel.attributes())
+            .any(|a| a.name == name)
+    }
+
+    /// Runs the cascade over the rules of `layer` with a selector accepted by `matches`.
+    /// `!important` beats specificity, specificity beats source order.
+    fn cascade(
+        &self,
+        layer: Option<&str>,
+        matches: impl Fn(&Selector) -> bool,
+    ) -> Vec<Applied<'_>> {
+        let mut winners: HashMap<&str, Applied> = HashMap::new();
 
-            if to_ignore {
+        for rule in self.rules.iter().filter(|r| r.layer.as_deref() == layer) {
+            let Some(selector) = rule
+                .selectors
+                .iter()
+                .filter(|sel| matches(sel))
+                .max_by_key(|sel| sel.specificity())
+            else {
                 continue;
             };
 
-            match c {
-                '{' => {
-                    scope_type = 1;
-                    continue;
-                }
-                ':' => {
-                    if scope_type == 1 {
-                        scope_type = 2;
-                        continue;
-                    }
-                }
-                ';' => {
-                    for sel in sel_acc.split(",") {
-                        stylesheet
-                            .entry(sel.to_string())
-                            .or_insert_with(|| {
-                                vec_keys.push(sel.to_string());
-                                HashMap::new()
-                            })
-                            .insert(key_acc.clone(), val_acc.clone());
-                    }
-
-                    key_acc = "".to_string();
-                    val_acc = "".to_string();
-                    scope_type = 1;
-                    continue;
-                }
-                '}' => {
-                    sel_acc = "".to_string();
-                    scope_type = 0;
+            for decl in &rule.declarations {
+                let candidate = Applied {
+                    name: &decl.name,
+                    value: &decl.value,
+                    important: decl.important,
+                    specificity: selector.specificity(),
+                    selector,
+                    span: decl.span,
+                };
+
+                // rules are visited in source order, so ties go to the later one
+                if let Some(current) = winners.get(candidate.name)
+                    && (current.important, current.specificity)
+                        > (candidate.important, candidate.specificity)
+                {
                     continue;
                 }
-                _ => {
-                    // nothing
-                }
-            };
 
-            match scope_type {
-                0 => {
-                    sel_acc.push(c);
-                }
-                1 => {
-                    key_acc.push(c);
-                }
-                2 => {
-                    val_acc.push(c);
-                }
-                _ => {
-                    panic!("error parsing css");
-                }
-            };
+                winners.insert(candidate.name, candidate);
+            }
         }
 
-        let keys = Rc::from_iter(vec_keys);
-
-        Self { stylesheet, keys }
+        let mut applied: Vec<Applied> = winners.into_values().collect();
+        applied.sort_by_key(|a| a.name);
+        applied
     }
 
     pub fn get_plugin_args(&self, name: String) -> Vec<String> {
-        let q = self.query(format!("#plugin.{}", name));
+        let q = self.cascade(None, |sel| {
+            sel.id() == Some("plugin") && sel.class_name().as_deref() == Some(name.as_str())
+        });
         let mut args: Vec<String> = vec![];
 
-        for k in q.keys() {
-            if !k.starts_with("-") {
+        for a in q {
+            if !a.name.starts_with("-") {
                 continue;
             }
 
-            let val = q.get(k);
-            if let Some(v) = val {
-                args.push(k.to_string());
-                args.push(v.to_string());
-            }
+            args.push(a.name.to_string());
+            args.push(a.value.to_string());
         }
 
         args
     }
 
-    pub fn query(&self, classes: String) -> HashMap<String, String> {
-        let mut applied = HashMap::new();
+    /// Which rule supplied each binding for `window`, outside any layer or inside `layer`.
+    pub fn explain(&self, window: &Window, layer: Option<&str>) -> Vec<Applied<'_>> {
+        self.cascade(layer, |sel| matches_window(sel, window))
+    }
 
-        if classes.starts_with("#plugin.") {
-            return match self.stylesheet.get(&classes) {
-                Some(r) => r.clone(),
-                None => HashMap::new(),
-            };
-        };
+    /// The bindings for `window`, with the `active` layers stacked on top of the rest.
+    /// A layer only replaces what it binds, everything else falls through.
+    pub fn query(&self, window: &Window, active: &[String]) -> HashMap<String, String> {
+        let mut query = HashMap::new();
 
-        let mut copy_from = |key: String| {
-            for (k, v) in &self.stylesheet[&key] {
-                applied.insert(k.as_str().to_string(), v.as_str().to_string());
+        let layers = self.layers();
+        let stacked = layers.iter().filter(|l| active.iter().any(|a| a == *l));
+        for layer in std::iter::once(None).chain(stacked.map(|l| Some(*l))) {
+            for a in self.explain(window, layer) {
+                query.insert(a.name.to_string(), a.value.to_string());
             }
-        };
+        }
 
-        for i in 0..self.keys.len() {
-            let key = self.keys[i].clone();
-            if key == "*" {
-                copy_from(key);
-                continue;
+        query
+    }
+
+    pub fn print(&self) {
+        for (i, rule) in self.rules.iter().enumerate() {
+            let indent = if rule.layer.is_some() { "  " } else { "" };
+            let prev = i.checked_sub(1).map(|p| &self.rules[p].layer);
+            let next = self.rules.get(i + 1).map(|r| &r.layer);
+
+            if let Some(layer) = &rule.layer
+                && prev != Some(&rule.layer)
+            {
+                println!("@layer {} {{", layer);
+            }
+
+            let selectors: Vec<String> = rule.selectors.iter().map(|s| s.to_string()).collect();
+            println!("{}{} {{", indent, selectors.join(", "));
+            for decl in &rule.declarations {
+                let important = if decl.important { " !important" } else { "" };
+                println!("{}  {}: {:?}{}", indent, decl.name, decl.value, important);
             }
+            println!("{}}}", indent);
 
-            if key.starts_with(classes.as_str()) {
-                copy_from(key);
+            if rule.layer.is_some() && next != Some(&rule.layer) {
+                println!("}}");
             }
         }
+    }
+}
 
-        applied
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(class: &str, title: &str) -> Window {
+        Window {
+            class: class.to_string(),
+            title: title.to_string(),
+            process: String::new(),
+        }
     }
 
-    pub fn print(&self) {
-        println!("{:#?}", self.stylesheet);
-        println!("{:#?}", self.keys);
+    fn get<'a>(query: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+        query.get(name).map(String::as_str)
+    }
+
+    const CASCADE: &str = r#"
+        .firefox[title*="Tube"] { a: "both"; }
+        .firefox { a: "class"; b: "class"; }
+        * { a: "any"; b: "any"; x: "any" !important; }
+        .firefox { x: "class"; }
+        [title*="Tube"] { b: "title"; }
+    "#;
+
+    #[test]
+    fn specificity_beats_source_order() {
+        let cfg = Config::load_from(CASCADE).unwrap();
+        let query = cfg.query(&window("firefox", "YouTube"), &[]);
+        assert_eq!(get(&query, "a"), Some("both"));
+
+        let query = cfg.query(&window("firefox", "Inbox"), &[]);
+        assert_eq!(get(&query, "a"), Some("class"));
+    }
+
+    #[test]
+    fn ties_go_to_the_later_rule() {
+        let cfg = Config::load_from(CASCADE).unwrap();
+        let query = cfg.query(&window("firefox", "YouTube"), &[]);
+        assert_eq!(get(&query, "b"), Some("title"));
+
+        let query = cfg.query(&window("kitty", "YouTube"), &[]);
+        assert_eq!(get(&query, "b"), Some("title"));
+    }
+
+    #[test]
+    fn important_beats_specificity() {
+        let cfg = Config::load_from(CASCADE).unwrap();
+        let query = cfg.query(&window("firefox", "YouTube"), &[]);
+        assert_eq!(get(&query, "x"), Some("any"));
+    }
+
+    #[test]
+    fn a_selector_list_counts_its_most_specific_match() {
+        let cfg = Config::load_from(
+            r#"
+            .kitty, .kitty[title] { a: "list"; }
+            [title] { a: "title"; }
+            "#,
+        )
+        .unwrap();
+        let applied = cfg.explain(&window("kitty", "zsh"), None);
+        assert_eq!(applied[0].value, "list");
+        assert_eq!(applied[0].specificity, Specificity(0, 2));
+    }
+
+    #[test]
+    fn unordered_chords_cascade_as_one_binding() {
+        let cfg = Config::load_from(r#"* { rb+lt: "first"; } * { lt+rb: "second"; }"#).unwrap();
+        let query = cfg.query(&Window::default(), &[]);
+        assert_eq!(get(&query, "lt+rb"), Some("second"));
+        assert_eq!(get(&query, "rb+lt"), None);
+    }
+
+    #[test]
+    fn layers_only_replace_what_they_bind() {
+        let cfg =
+            Config::load_from(r#"* { a: "base"; b: "base"; } @layer nav { a: "nav"; }"#).unwrap();
+        let query = cfg.query(&Window::default(), &["nav".to_string()]);
+        assert_eq!(get(&query, "a"), Some("nav"));
+        assert_eq!(get(&query, "b"), Some("base"));
+        let query = cfg.query(&Window::default(), &[]);
+        assert_eq!(get(&query, "a"), Some("base"));
+    }
+
+    #[test]
+    fn later_bare_declarations_win_ties_in_a_layer() {
+        let cfg = Config::load_from(r#"@layer nav { a: "first"; * { a: "middle"; } a: "last"; }"#)
+            .unwrap();
+        let query = cfg.query(&Window::default(), &["nav".to_string()]);
+        assert_eq!(query.get("a").map(String::as_str), Some("last"));
     }
 }
@@ -0,0 +1,528 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::settings::{Settings, parse_duration};
+
+/// What a binding name like `a:hold` asks of a press.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    /// `:tap`, released before any hold fired
+    Tap,
+    /// `:hold`, held for `--hold-time`
+    Hold,
+    /// `:double`, pressed again within `--double-tap-time` of a tap
+    Double,
+    /// `:long(800ms)`, held for the given time
+    Long(Duration),
+    /// `:half`, an analog trigger released without going all the way down
+    Half,
+    /// `:full`, an analog trigger pressed all the way down
+    Full,
+}
+
+impl Gesture {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "tap" => Ok(Gesture::Tap),
+            "hold" => Ok(Gesture::Hold),
+            "double" => Ok(Gesture::Double),
+            "half" => Ok(Gesture::Half),
+            "full" => Ok(Gesture::Full),
+            _ => match s.strip_prefix("long(").and_then(|d| d.strip_suffix(')')) {
+                Some(d) => parse_duration(d).map(Gesture::Long),
+                None => Err(format!(
+                    "unknown gesture `:{}`, expected :tap, :hold, :double, :long(<time>), :half or :full",
+                    s
+                )),
+            },
+        }
+    }
+
+    fn threshold(&self, settings: &Settings) -> Option<Duration> {
+        match self {
+            Gesture::Hold => Some(settings.hold_time),
+            Gesture::Long(d) => Some(*d),
+            Gesture::Tap | Gesture::Double | Gesture::Half | Gesture::Full => None,
+        }
+    }
+}
+
+/// `rb-a:long(800ms)` is the chord `rb-a` with a `:long(800ms)` gesture.
+pub fn split_gesture(name: &str) -> (&str, Option<Result<Gesture, String>>) {
+    match name.split_once(':') {
+        Some((chord, gesture)) => (chord, Some(Gesture::parse(gesture))),
+        None => (name, None),
+    }
+}
+
+/// Every gesture binding for `chord`, as `(binding name, gesture)`.
+fn gestures_for<'a>(chord: &str, bindings: &'a HashMap<String, String>) -> Vec<(&'a str, Gesture)> {
+    bindings
+        .keys()
+        .filter_map(|name| match split_gesture(name) {
+            (c, Some(Ok(gesture))) if c == chord => Some((name.as_str(), gesture)),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn has_gestures(chord: &str, bindings: &HashMap<String, String>) -> bool {
+    !gestures_for(chord, bindings).is_empty()
+}
+
+/// The longest hold `chord` has a binding for.
+fn longest_hold<'a>(
+    chord: &str,
+    bindings: &'a HashMap<String, String>,
+    settings: &Settings,
+) -> Option<(&'a str, Duration)> {
+    gestures_for(chord, bindings)
+        .into_iter()
+        .filter_map(|(name, g)| Some((name, g.threshold(settings)?)))
+        .max_by_key(|(_, t)| *t)
+}
+
+/// A binding `Gestures` picked.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fired {
+    pub name: String,
+    /// looked up when it was picked, a tap held back for a `:double` keeps the value the
+    /// layers on at its release gave it
+    pub value: String,
+    /// the button that formed the chord, if it's still held
+    pub button: Option<String>,
+}
+
+fn fired(name: &str, bindings: &HashMap<String, String>, button: Option<&str>) -> Option<Fired> {
+    Some(Fired {
+        name: name.to_string(),
+        value: bindings.get(name)?.clone(),
+        button: button.map(str::to_string),
+    })
+}
+
+struct Held {
+    chord: String,
+    /// the button whose press formed the chord
+    button: String,
+    since: Instant,
+    /// a hold or double already fired for this press, so releasing it does nothing
+    fired: bool,
+}
+
+struct PendingTap {
+    chord: String,
+    binding: Option<Fired>,
+    /// `None` when the double tap window is too long to ever run out
+    expires: Option<Instant>,
+}
+
+/// Tells taps, holds, double taps, long presses and half or full trigger presses apart for
+/// chords that have gesture bindings. Chords without any are left to the caller. A binding is
+/// only held back while a competing one could still match: a tap waits for a possible
+/// `:double`, a hold waits for a longer hold.
+#[derive(Default)]
+pub struct Gestures {
+    held: Option<Held>,
+    pending_tap: Option<PendingTap>,
+}
+
+impl Gestures {
+    /// `chord` was just formed by pressing `button`. Returns the bindings to fire.
+    pub fn press(
+        &mut self,
+        chord: &str,
+        button: &str,
+        now: Instant,
+        bindings: &HashMap<String, String>,
+    ) -> Vec<Fired> {
+        let mut out = vec![];
+
+        if let Some(tap) = self.pending_tap.take() {
+            // a press that comes in before `poll` noticed the window ran out is too late too
+            let in_time = tap.expires.is_none_or(|e| now < e);
+            if tap.chord == chord && in_time {
+                let double = gestures_for(chord, bindings)
+                    .into_iter()
+                    .find(|(_, g)| *g == Gesture::Double);
+                if let Some((name, _)) = double {
+                    out.extend(fired(name, bindings, Some(button)));
+                    self.held = Some(Held {
+                        chord: chord.to_string(),
+                        button: button.to_string(),
+                        since: now,
+                        fired: true,
+                    });
+                    return out;
+                }
+            }
+
+            // something else was pressed, so the tap is final
+            out.extend(tap.binding);
+        }
+
+        self.held = Some(Held {
+            chord: chord.to_string(),
+            button: button.to_string(),
+            since: now,
+            fired: false,
+        });
+        out
+    }
+
+    /// The held chord ended because one of its buttons was released. Returns the bindings to fire.
+    pub fn release(
+        &mut self,
+        now: Instant,
+        bindings: &HashMap<String, String>,
+        settings: &Settings,
+    ) -> Vec<Fired> {
+        let Some(held) = self.held.take() else {
+            return vec![];
+        };
+
+        let gestures = gestures_for(&held.chord, bindings);
+        if held.fired || gestures.is_empty() {
+            return vec![];
+        }
+
+        // the longest hold this press made it to
+        let elapsed = now.duration_since(held.since);
+        let reached = gestures
+            .iter()
+            .filter_map(|(name, g)| Some((*name, g.threshold(settings)?)))
+            .filter(|(_, t)| *t <= elapsed)
+            .max_by_key(|(_, t)| *t);
+        if let Some((name, _)) = reached {
+            return fired(name, bindings, None).into_iter().collect();
+        }
+
+        // a plain binding on the chord doubles as its tap, and a trigger's tap is its half press
+        let tap = gestures
+            .iter()
+            .find(|(_, g)| matches!(g, Gesture::Tap | Gesture::Half))
+            .map(|(name, _)| *name)
+            .or(Some(held.chord.as_str()))
+            .and_then(|name| fired(name, bindings, None));
+
+        if gestures.iter().any(|(_, g)| *g == Gesture::Double) {
+            self.pending_tap = Some(PendingTap {
+                chord: held.chord,
+                binding: tap,
+                expires: now.checked_add(settings.double_tap_time),
+            });
+            return vec![];
+        }
+
+        tap.into_iter().collect()
+    }
+
+    /// Fires taps that can no longer become doubles, and holds nothing longer competes with.
+    pub fn poll(
+        &mut self,
+        now: Instant,
+        bindings: &HashMap<String, String>,
+        settings: &Settings,
+    ) -> Vec<Fired> {
+        let mut out = vec![];
+
+        if self
+            .pending_tap
+            .as_ref()
+            .is_some_and(|t| t.expires.is_some_and(|e| now >= e))
+            && let Some(tap) = self.pending_tap.take()
+        {
+            out.extend(tap.binding);
+        }
+
+        if let Some(held) = &mut self.held
+            && !held.fired
+            && let Some((name, t)) = longest_hold(&held.chord, bindings, settings)
+            && now.duration_since(held.since) >= t
+        {
+            out.extend(fired(name, bindings, Some(&held.button)));
+            held.fired = true;
+        }
+
+        out
+    }
+
+    /// The held chord's trigger went all the way down. Returns the `:full` binding to fire.
+    pub fn full(&mut self, bindings: &HashMap<String, String>) -> Vec<Fired> {
+        let Some(held) = self.held.as_mut().filter(|h| !h.fired) else {
+            return vec![];
+        };
+
+        let full = gestures_for(&held.chord, bindings)
+            .into_iter()
+            .find(|(_, g)| *g == Gesture::Full);
+        match full {
+            Some((name, _)) => {
+                held.fired = true;
+                fired(name, bindings, Some(&held.button))
+                    .into_iter()
+                    .collect()
+            }
+            None => vec![],
+        }
+    }
+
+    /// When `poll` next has something to do.
+    pub fn next_deadline(
+        &self,
+        bindings: &HashMap<String, String>,
+        settings: &Settings,
+    ) -> Option<Instant> {
+        let tap = self.pending_tap.as_ref().and_then(|t| t.expires);
+
+        let hold = self.held.as_ref().and_then(|held| {
+            if held.fired {
+                return None;
+            }
+            let (_, t) = longest_hold(&held.chord, bindings, settings)?;
+            held.since.checked_add(t)
+        });
+
+        tap.into_iter().chain(hold).min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bindings(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, val)| (name.to_string(), val.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn a_held_back_tap_keeps_the_value_it_was_released_with() {
+        let settings = Settings::default();
+        let layer = bindings(&[("a:tap", "layer tap"), ("a:double", "layer double")]);
+        let base = bindings(&[("a:tap", "base tap")]);
+        let start = Instant::now();
+        let mut gestures = Gestures::default();
+
+        assert!(gestures.press("a", "a", start, &layer).is_empty());
+        assert!(gestures.release(start, &layer, &settings).is_empty());
+
+        // the one-shot layer ended with the chord, the tap still fires what it had
+        let late = start + settings.double_tap_time;
+        assert_eq!(
+            gestures.poll(late, &base, &settings),
+            [Fired {
+                name: "a:tap".to_string(),
+                value: "layer tap".to_string(),
+                button: None,
+            }]
+        );
+        assert!(gestures.poll(late, &base, &settings).is_empty());
+    }
+
+    #[test]
+    fn fired_bindings_belong_to_the_button_still_held() {
+        let settings = Settings::default();
+        let b = bindings(&[
+            ("rb-a:tap", "tap"),
+            ("rb-a:double", "double"),
+            ("rb-a:hold", "hold(shift)"),
+            ("x", "x"),
+        ]);
+        let start = Instant::now();
+        let mut gestures = Gestures::default();
+
+        gestures.press("rb-a", "a", start, &b);
+        let held = gestures.poll(start + settings.hold_time, &b, &settings);
+        assert_eq!(held[0].button.as_deref(), Some("a"));
+        gestures.release(start + settings.hold_time, &b, &settings);
+
+        // a tap flushed by another button's press fires after its own button came up
+        let later = start + settings.hold_time * 2;
+        gestures.press("rb-a", "a", later, &b);
+        gestures.release(later, &b, &settings);
+        let flushed = gestures.press("x", "x", later, &b);
+        assert_eq!(flushed[0].name, "rb-a:tap");
+        assert_eq!(flushed[0].button, None);
+    }
+
+    fn names(fired: Vec<Fired>) -> Vec<String> {
+        fired.into_iter().map(|f| f.name).collect()
+    }
+
+    #[test]
+    fn gestures_are_parsed() {
+        assert_eq!(Gesture::parse("tap"), Ok(Gesture::Tap));
+        assert_eq!(Gesture::parse("full"), Ok(Gesture::Full));
+        assert_eq!(
+            Gesture::parse("long(800ms)"),
+            Ok(Gesture::Long(Duration::from_millis(800)))
+        );
+        assert!(Gesture::parse("long(soon)").is_err());
+        assert!(Gesture::parse("triple").is_err());
+        assert_eq!(
+            split_gesture("rb-a:hold"),
+            ("rb-a", Some(Ok(Gesture::Hold)))
+        );
+        assert_eq!(split_gesture("rb-a"), ("rb-a", None));
+    }
+
+    #[test]
+    fn a_tap_becomes_a_hold_at_the_threshold() {
+        let settings = Settings::default();
+        let b = bindings(&[("a:tap", "tap"), ("a:hold", "hold")]);
+        let start = Instant::now();
+        let mut gestures = Gestures::default();
+
+        let just_before = start + settings.hold_time - Duration::from_millis(1);
+        gestures.press("a", "a", start, &b);
+        assert!(gestures.poll(just_before, &b, &settings).is_empty());
+        assert_eq!(
+            names(gestures.release(just_before, &b, &settings)),
+            ["a:tap"]
+        );
+
+        gestures.press("a", "a", start, &b);
+        assert_eq!(
+            gestures.next_deadline(&b, &settings),
+            Some(start + settings.hold_time)
+        );
+        assert_eq!(
+            names(gestures.poll(start + settings.hold_time, &b, &settings)),
+            ["a:hold"]
+        );
+        // it already fired, letting go does nothing more
+        assert!(
+            gestures
+                .release(start + settings.hold_time, &b, &settings)
+                .is_empty()
+        );
+
+        // released at the threshold before a poll noticed, it's still a hold
+        gestures.press("a", "a", start, &b);
+        assert_eq!(
+            names(gestures.release(start + settings.hold_time, &b, &settings)),
+            ["a:hold"]
+        );
+    }
+
+    #[test]
+    fn a_second_tap_in_the_window_is_a_double() {
+        let settings = Settings::default();
+        let b = bindings(&[("a:tap", "tap"), ("a:double", "double")]);
+        let start = Instant::now();
+        let mut gestures = Gestures::default();
+
+        gestures.press("a", "a", start, &b);
+        assert!(gestures.release(start, &b, &settings).is_empty());
+        assert_eq!(
+            gestures.next_deadline(&b, &settings),
+            Some(start + settings.double_tap_time)
+        );
+
+        let again = start + settings.double_tap_time - Duration::from_millis(1);
+        assert_eq!(names(gestures.press("a", "a", again, &b)), ["a:double"]);
+        assert!(gestures.release(again, &b, &settings).is_empty());
+        assert!(
+            gestures
+                .poll(again + settings.double_tap_time, &b, &settings)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn a_second_tap_outside_the_window_is_another_tap() {
+        let settings = Settings::default();
+        let b = bindings(&[("a:tap", "tap"), ("a:double", "double")]);
+        let start = Instant::now();
+        let mut gestures = Gestures::default();
+
+        gestures.press("a", "a", start, &b);
+        gestures.release(start, &b, &settings);
+        let late = start + settings.double_tap_time;
+        assert_eq!(names(gestures.poll(late, &b, &settings)), ["a:tap"]);
+        assert!(gestures.press("a", "a", late, &b).is_empty());
+        gestures.release(late, &b, &settings);
+
+        // pressed again after the window but before a poll, the first tap is just final
+        let later = late + settings.double_tap_time;
+        assert_eq!(names(gestures.press("a", "a", later, &b)), ["a:tap"]);
+        assert!(gestures.release(later, &b, &settings).is_empty());
+        assert_eq!(
+            names(gestures.poll(later + settings.double_tap_time, &b, &settings)),
+            ["a:tap"]
+        );
+    }
+
+    #[test]
+    fn the_longest_hold_reached_wins() {
+        let settings = Settings::default();
+        let b = bindings(&[("a", "plain"), ("a:hold", "hold"), ("a:long(1s)", "long")]);
+        let start = Instant::now();
+        let mut gestures = Gestures::default();
+
+        // the hold waits in case the press makes it to the long one
+        gestures.press("a", "a", start, &b);
+        assert!(
+            gestures
+                .poll(start + settings.hold_time, &b, &settings)
+                .is_empty()
+        );
+        assert_eq!(
+            gestures.next_deadline(&b, &settings),
+            Some(start + Duration::from_secs(1))
+        );
+        assert_eq!(
+            names(gestures.release(start + Duration::from_millis(500), &b, &settings)),
+            ["a:hold"]
+        );
+
+        gestures.press("a", "a", start, &b);
+        assert_eq!(
+            names(gestures.poll(start + Duration::from_secs(1), &b, &settings)),
+            ["a:long(1s)"]
+        );
+        gestures.release(start + Duration::from_secs(2), &b, &settings);
+
+        // a plain binding is the tap
+        gestures.press("a", "a", start, &b);
+        assert_eq!(names(gestures.release(start, &b, &settings)), ["a"]);
+    }
+
+    #[test]
+    fn another_press_flushes_a_pending_tap() {
+        let settings = Settings::default();
+        let b = bindings(&[("a:tap", "tap"), ("a:double", "double"), ("b:hold", "hold")]);
+        let start = Instant::now();
+        let mut gestures = Gestures::default();
+
+        gestures.press("a", "a", start, &b);
+        gestures.release(start, &b, &settings);
+        assert_eq!(names(gestures.press("b", "b", start, &b)), ["a:tap"]);
+        // only the hold is left to fire, the tap went with the press
+        assert_eq!(
+            names(gestures.poll(start + settings.double_tap_time, &b, &settings)),
+            ["b:hold"]
+        );
+    }
+
+    #[test]
+    fn full_fires_once_per_press() {
+        let settings = Settings::default();
+        let b = bindings(&[("lb:half", "half"), ("lb:full", "full")]);
+        let start = Instant::now();
+        let mut gestures = Gestures::default();
+
+        gestures.press("lb", "lb", start, &b);
+        assert_eq!(names(gestures.full(&b)), ["lb:full"]);
+        assert!(gestures.full(&b).is_empty());
+        assert!(gestures.release(start, &b, &settings).is_empty());
+
+        gestures.press("lb", "lb", start, &b);
+        assert_eq!(names(gestures.release(start, &b, &settings)), ["lb:half"]);
+    }
+}
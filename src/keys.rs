@@ -0,0 +1,227 @@
+use rdev::Key;
+
+/// Keys held down while the rest of a combo is sent. The first name is the one shown in
+/// `orchidbox keys`, the rest are aliases.
+const MODIFIERS: &[(&[&str], Key)] = &[
+    (&["ctrl", "control", "lctrl"], Key::ControlLeft),
+    (&["rctrl"], Key::ControlRight),
+    (&["shift", "lshift"], Key::ShiftLeft),
+    (&["rshift"], Key::ShiftRight),
+    (&["alt", "lalt", "option"], Key::Alt),
+    (&["altgr", "ralt"], Key::AltGr),
+    (&["meta", "super", "win", "cmd", "lmeta"], Key::MetaLeft),
+    (&["rmeta", "rsuper", "rwin", "rcmd"], Key::MetaRight),
+];
+
+/// Keys pressed and released in turn.
+const NAMED: &[(&[&str], Key)] = &[
+    // arrows
+    (&["up", "uparrow"], Key::UpArrow),
+    (&["down", "downarrow"], Key::DownArrow),
+    (&["left", "leftarrow"], Key::LeftArrow),
+    (&["right", "rightarrow"], Key::RightArrow),
+    // editing and navigation
+    (&["tab"], Key::Tab),
+    (&["enter", "return"], Key::Return),
+    (&["esc", "escape"], Key::Escape),
+    (&["space", "spacebar"], Key::Space),
+    (&["backspace", "bksp"], Key::Backspace),
+    (&["delete", "del"], Key::Delete),
+    (&["insert", "ins"], Key::Insert),
+    (&["home"], Key::Home),
+    (&["end"], Key::End),
+    (&["pageup", "pgup"], Key::PageUp),
+    (&["pagedown", "pgdn", "pgdown"], Key::PageDown),
+    // locks and system keys
+    (&["capslock", "caps"], Key::CapsLock),
+    (&["numlock"], Key::NumLock),
+    (&["scrolllock"], Key::ScrollLock),
+    (&["printscreen", "print", "prtsc"], Key::PrintScreen),
+    (&["pause", "break"], Key::Pause),
+    // function keys
+    (&["f1"], Key::F1),
+    (&["f2"], Key::F2),
+    (&["f3"], Key::F3),
+    (&["f4"], Key::F4),
+    (&["f5"], Key::F5),
+    (&["f6"], Key::F6),
+    (&["f7"], Key::F7),
+    (&["f8"], Key::F8),
+    (&["f9"], Key::F9),
+    (&["f10"], Key::F10),
+    (&["f11"], Key::F11),
+    (&["f12"], Key::F12),
+    // keypad
+    (&["kp0"], Key::Kp0),
+    (&["kp1"], Key::Kp1),
+    (&["kp2"], Key::Kp2),
+    (&["kp3"], Key::Kp3),
+    (&["kp4"], Key::Kp4),
+    (&["kp5"], Key::Kp5),
+    (&["kp6"], Key::Kp6),
+    (&["kp7"], Key::Kp7),
+    (&["kp8"], Key::Kp8),
+    (&["kp9"], Key::Kp9),
+    (&["kpenter", "kpreturn"], Key::KpReturn),
+    (&["kpplus"], Key::KpPlus),
+    (&["kpminus"], Key::KpMinus),
+    (&["kpmultiply", "kpstar"], Key::KpMultiply),
+    (&["kpdivide", "kpslash"], Key::KpDivide),
+    (&["kpdelete", "kpdot", "kpdecimal"], Key::KpDelete),
+    // punctuation, by the unshifted character on a US layout
+    (&["minus", "dash", "hyphen"], Key::Minus),
+    (&["equal", "equals"], Key::Equal),
+    (&["leftbracket", "lbracket"], Key::LeftBracket),
+    (&["rightbracket", "rbracket"], Key::RightBracket),
+    (&["semicolon"], Key::SemiColon),
+    (&["quote", "apostrophe"], Key::Quote),
+    (&["backquote", "grave", "backtick"], Key::BackQuote),
+    (&["backslash"], Key::BackSlash),
+    (&["intlbackslash"], Key::IntlBackslash),
+    (&["comma"], Key::Comma),
+    (&["dot", "period"], Key::Dot),
+    (&["slash"], Key::Slash),
+];
+
+/// Keys rdev has no `Key` for, by their Linux input event code (`KEY_*` in
+/// `linux/input-event-codes.h`).
+const EXTENDED: &[(&[&str], u32)] = &[
+    (&["f13"], 183),
+    (&["f14"], 184),
+    (&["f15"], 185),
+    (&["f16"], 186),
+    (&["f17"], 187),
+    (&["f18"], 188),
+    (&["f19"], 189),
+    (&["f20"], 190),
+    (&["f21"], 191),
+    (&["f22"], 192),
+    (&["f23"], 193),
+    (&["f24"], 194),
+];
+
+/// Media keys, also by event code. No plain words like `next` or `stop`, plugin output goes
+/// through the same names and dictating those should type them.
+const MEDIA: &[(&[&str], u32)] = &[
+    (&["volume-up", "vol-up"], 115),
+    (&["volume-down", "vol-down"], 114),
+    (&["volume-mute", "mute"], 113),
+    (&["play-pause"], 164),
+    (&["stop-media"], 166),
+    (&["next-track"], 163),
+    (&["prev-track", "previous-track"], 165),
+    (&["brightness-up"], 225),
+    (&["brightness-down"], 224),
+];
+
+/// How rdev sends a key by event code: X11 keycodes are event codes plus 8, and Windows has
+/// virtual key codes for some of them. `None` where there's no way to send it.
+fn raw_key(code: u32) -> Option<Key> {
+    #[cfg(target_os = "linux")]
+    return Some(Key::Unknown(code + 8));
+
+    #[cfg(target_os = "windows")]
+    return match code {
+        183..=194 => Some(Key::Unknown(0x7C + code - 183)),
+        113 => Some(Key::Unknown(0xAD)),
+        114 => Some(Key::Unknown(0xAE)),
+        115 => Some(Key::Unknown(0xAF)),
+        163 => Some(Key::Unknown(0xB0)),
+        165 => Some(Key::Unknown(0xB1)),
+        166 => Some(Key::Unknown(0xB2)),
+        164 => Some(Key::Unknown(0xB3)),
+        _ => None,
+    };
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    return None;
+}
+
+/// Names match in any case and with or without `-` or `_`, so `PageUp`, `page_up` and
+/// `page-up` are all `pageup`. Spaces stay, `page up` is text.
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, '-' | '_'))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+fn lookup<T: Copy>(table: &[(&[&str], T)], name: &str) -> Option<T> {
+    let name = normalize(name);
+    table
+        .iter()
+        .find(|(names, _)| names.iter().any(|n| normalize(n) == name))
+        .map(|(_, key)| *key)
+}
+
+pub fn modifier_key(name: &str) -> Option<Key> {
+    lookup(MODIFIERS, name)
+}
+
+pub fn named_key(name: &str) -> Option<Key> {
+    // a lone `-` is the character, not a name
+    if name.chars().count() < 2 {
+        return None;
+    }
+    lookup(NAMED, name)
+        .or_else(|| lookup(EXTENDED, name).and_then(raw_key))
+        .or_else(|| lookup(MEDIA, name).and_then(raw_key))
+}
+
+/// Prints every key name as a markdown table, for `orchidbox keys`.
+pub fn print_reference() {
+    println!("| Name | Aliases | Kind |");
+    println!("| ---- | ------- | ---- |");
+
+    let row = |names: &[&str], kind: &str| {
+        let aliases: Vec<String> = names[1..].iter().map(|a| format!("`{}`", a)).collect();
+        println!("| `{}` | {} | {} |", names[0], aliases.join(", "), kind);
+    };
+
+    for (names, _) in MODIFIERS {
+        row(names, "modifier");
+    }
+    for (names, _) in NAMED {
+        row(names, "key");
+    }
+    // only what this platform can send
+    for (names, code) in EXTENDED {
+        if raw_key(*code).is_some() {
+            row(names, "key");
+        }
+    }
+    for (names, code) in MEDIA {
+        if raw_key(*code).is_some() {
+            row(names, "media");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_match_in_any_spelling() {
+        for name in ["PageUp", "page_up", "page-up", "PGUP"] {
+            assert_eq!(named_key(name), Some(Key::PageUp), "{}", name);
+        }
+        assert_eq!(modifier_key("Ctrl"), Some(Key::ControlLeft));
+    }
+
+    #[test]
+    fn words_are_not_names() {
+        for text in ["page up", "next", "play", "stop", "prev", "-", "a"] {
+            assert_eq!(named_key(text), None, "{}", text);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn media_keys_are_raw_keycodes() {
+        assert_eq!(named_key("next-track"), Some(Key::Unknown(163 + 8)));
+        assert_eq!(named_key("Volume_Mute"), Some(Key::Unknown(113 + 8)));
+        assert_eq!(named_key("mute"), named_key("volume-mute"));
+        assert_eq!(named_key("f13"), Some(Key::Unknown(183 + 8)));
+    }
+}
@@ -0,0 +1,252 @@
+use crate::config::BASE_LAYER;
+
+/// A value that switches layers, e.g. `toggle-layer(nav)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayerAction<'a> {
+    /// `layer(nav)`, on while the button is held
+    Momentary(&'a str),
+    /// `toggle-layer(nav)`
+    Toggle(&'a str),
+    /// `one-shot-layer(nav)`, on for the next chord only
+    OneShot(&'a str),
+    /// `to-layer(nav)`, turns every other layer off, `to-layer(base)` turns them all off
+    To(&'a str),
+}
+
+impl<'a> LayerAction<'a> {
+    pub fn parse(val: &'a str) -> Option<Self> {
+        let (func, rest) = val.split_once('(')?;
+        let name = rest
+            .strip_suffix(')')?
+            .trim()
+            .trim_matches(|c| c == '"' || c == '\'');
+
+        match func.trim() {
+            "layer" => Some(LayerAction::Momentary(name)),
+            "toggle-layer" => Some(LayerAction::Toggle(name)),
+            "one-shot-layer" => Some(LayerAction::OneShot(name)),
+            "to-layer" => Some(LayerAction::To(name)),
+            _ => None,
+        }
+    }
+
+    pub fn layer(&self) -> &'a str {
+        match self {
+            LayerAction::Momentary(l)
+            | LayerAction::Toggle(l)
+            | LayerAction::OneShot(l)
+            | LayerAction::To(l) => l,
+        }
+    }
+}
+
+struct OneShot {
+    layer: String,
+    /// a button was pressed with the layer on, so it ends with that chord
+    used: bool,
+}
+
+/// Which `@layer`s are on. How they stack is up to `Config::query`.
+#[derive(Default)]
+pub struct Layers {
+    /// `(button, layer)`, each on until its button is released
+    momentary: Vec<(String, String)>,
+    toggled: Vec<String>,
+    one_shot: Option<OneShot>,
+    changed: bool,
+}
+
+impl Layers {
+    pub fn active(&self) -> Vec<String> {
+        let mut active = self.toggled.clone();
+        active.extend(self.momentary.iter().map(|(_, l)| l.clone()));
+        active.extend(self.one_shot.iter().map(|o| o.layer.clone()));
+        active
+    }
+
+    /// Whether `button` has a layer on, which takes it out of chords while it's held.
+    pub fn holding(&self, button: &str) -> bool {
+        self.momentary.iter().any(|(b, _)| b == button)
+    }
+
+    /// Turns `layer` on until `button` is released.
+    pub fn hold(&mut self, button: &str, layer: &str) {
+        self.momentary.push((button.to_string(), layer.to_string()));
+        self.changed = true;
+    }
+
+    pub fn release(&mut self, button: &str) {
+        let before = self.momentary.len();
+        self.momentary.retain(|(b, _)| b != button);
+        self.changed |= self.momentary.len() != before;
+    }
+
+    /// Runs an action that fires once. `layer(...)` needs a held button, so it's left to `hold`.
+    pub fn run(&mut self, action: LayerAction) {
+        match action {
+            LayerAction::Momentary(_) => return,
+            LayerAction::Toggle(layer) => {
+                if let Some(pos) = self.toggled.iter().position(|l| l == layer) {
+                    self.toggled.remove(pos);
+                } else {
+                    self.toggled.push(layer.to_string());
+                }
+            }
+            LayerAction::OneShot(layer) => {
+                self.one_shot = Some(OneShot {
+                    layer: layer.to_string(),
+                    used: false,
+                });
+            }
+            LayerAction::To(layer) => {
+                self.toggled.clear();
+                self.one_shot = None;
+                if layer != BASE_LAYER {
+                    self.toggled.push(layer.to_string());
+                }
+            }
+        }
+        self.changed = true;
+    }
+
+    /// A button went down, so a one-shot layer now has its chord.
+    pub fn pressed(&mut self) {
+        if let Some(one_shot) = &mut self.one_shot {
+            one_shot.used = true;
+        }
+    }
+
+    /// Every button is up, which ends a one-shot layer that was used.
+    pub fn chord_ended(&mut self) {
+        if self.one_shot.as_ref().is_some_and(|o| o.used) {
+            self.one_shot = None;
+            self.changed = true;
+        }
+    }
+
+    /// Drops everything that depends on buttons being held, toggled layers stay on.
+    pub fn release_all(&mut self) {
+        self.changed |= !self.momentary.is_empty() || self.one_shot.is_some();
+        self.momentary.clear();
+        self.one_shot = None;
+    }
+
+    /// Whether the active layers changed since the last call.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actions_are_parsed() {
+        assert_eq!(
+            LayerAction::parse("layer(nav)"),
+            Some(LayerAction::Momentary("nav"))
+        );
+        assert_eq!(
+            LayerAction::parse("toggle-layer( \"nav\" )"),
+            Some(LayerAction::Toggle("nav"))
+        );
+        assert_eq!(
+            LayerAction::parse("one-shot-layer('sym')"),
+            Some(LayerAction::OneShot("sym"))
+        );
+        assert_eq!(
+            LayerAction::parse("to-layer(base)"),
+            Some(LayerAction::To("base"))
+        );
+        assert_eq!(LayerAction::parse("layer(nav"), None);
+        assert_eq!(LayerAction::parse("press(a)"), None);
+    }
+
+    #[test]
+    fn momentary_layers_last_while_their_button_is_held() {
+        let mut layers = Layers::default();
+        layers.run(LayerAction::Momentary("nav"));
+        assert!(!layers.take_changed());
+
+        layers.hold("lt", "nav");
+        layers.hold("rt", "sym");
+        assert!(layers.take_changed());
+        assert!(layers.holding("lt"));
+        assert_eq!(layers.active(), ["nav", "sym"]);
+
+        // letting go of another button changes nothing
+        layers.release("a");
+        assert!(!layers.take_changed());
+
+        layers.release("lt");
+        assert!(layers.take_changed());
+        assert!(!layers.holding("lt"));
+        assert_eq!(layers.active(), ["sym"]);
+    }
+
+    #[test]
+    fn toggles_flip() {
+        let mut layers = Layers::default();
+        layers.run(LayerAction::Toggle("nav"));
+        layers.run(LayerAction::Toggle("sym"));
+        assert!(layers.take_changed());
+        assert_eq!(layers.active(), ["nav", "sym"]);
+
+        layers.run(LayerAction::Toggle("nav"));
+        assert!(layers.take_changed());
+        assert_eq!(layers.active(), ["sym"]);
+
+        // they outlast everything held
+        layers.hold("lt", "fn");
+        layers.release_all();
+        assert_eq!(layers.active(), ["sym"]);
+    }
+
+    #[test]
+    fn one_shots_last_for_the_next_chord() {
+        let mut layers = Layers::default();
+        layers.run(LayerAction::OneShot("sym"));
+        assert!(layers.take_changed());
+
+        // the chord that turned it on doesn't use it up
+        layers.chord_ended();
+        assert!(!layers.take_changed());
+        assert_eq!(layers.active(), ["sym"]);
+
+        layers.pressed();
+        layers.pressed();
+        assert_eq!(layers.active(), ["sym"]);
+        layers.chord_ended();
+        assert!(layers.take_changed());
+        assert!(layers.active().is_empty());
+
+        // a new one-shot replaces an unused one
+        layers.run(LayerAction::OneShot("sym"));
+        layers.run(LayerAction::OneShot("nav"));
+        assert_eq!(layers.active(), ["nav"]);
+        layers.release_all();
+        assert!(layers.active().is_empty());
+    }
+
+    #[test]
+    fn to_layer_turns_the_others_off() {
+        let mut layers = Layers::default();
+        layers.run(LayerAction::Toggle("nav"));
+        layers.run(LayerAction::Toggle("sym"));
+        layers.run(LayerAction::OneShot("fn"));
+        layers.hold("lt", "mouse");
+
+        layers.run(LayerAction::To("game"));
+        assert_eq!(layers.active(), ["game", "mouse"]);
+
+        layers.take_changed();
+        layers.run(LayerAction::To(BASE_LAYER));
+        assert!(layers.take_changed());
+        assert_eq!(layers.active(), ["mouse"]);
+
+        // the button holding a layer still lets it go
+        layers.release("lt");
+        assert!(layers.active().is_empty());
+    }
+}
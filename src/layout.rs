@@ -0,0 +1,489 @@
+use std::{
+    fmt,
+    process::Command,
+    sync::{
+        OnceLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use rdev::Key;
+
+/// A keyboard layout: what each key types on each level, in the order plain, shift, AltGr and
+/// shift+AltGr. A combining accent like `\u{301}` is a dead key for that accent, unless
+/// `dead_keys` is off, as in XKB's `nodeadkeys` variants, where it types the accent itself.
+/// Space, enter and tab are the same everywhere and left out.
+#[derive(PartialEq)]
+pub struct Layout {
+    /// the XKB name, with the variant in parentheses like `de(nodeadkeys)`
+    pub name: &'static str,
+    keys: &'static [(Key, &'static str)],
+    dead_keys: bool,
+}
+
+impl fmt::Debug for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+static US: Layout = Layout {
+    name: "us",
+    dead_keys: true,
+    keys: &[
+        (Key::BackQuote, "`~"),
+        (Key::Num1, "1!"),
+        (Key::Num2, "2@"),
+        (Key::Num3, "3#"),
+        (Key::Num4, "4$"),
+        (Key::Num5, "5%"),
+        (Key::Num6, "6^"),
+        (Key::Num7, "7&"),
+        (Key::Num8, "8*"),
+        (Key::Num9, "9("),
+        (Key::Num0, "0)"),
+        (Key::Minus, "-_"),
+        (Key::Equal, "=+"),
+        (Key::KeyQ, "qQ"),
+        (Key::KeyW, "wW"),
+        (Key::KeyE, "eE"),
+        (Key::KeyR, "rR"),
+        (Key::KeyT, "tT"),
+        (Key::KeyY, "yY"),
+        (Key::KeyU, "uU"),
+        (Key::KeyI, "iI"),
+        (Key::KeyO, "oO"),
+        (Key::KeyP, "pP"),
+        (Key::LeftBracket, "[{"),
+        (Key::RightBracket, "]}"),
+        (Key::BackSlash, "\\|"),
+        (Key::KeyA, "aA"),
+        (Key::KeyS, "sS"),
+        (Key::KeyD, "dD"),
+        (Key::KeyF, "fF"),
+        (Key::KeyG, "gG"),
+        (Key::KeyH, "hH"),
+        (Key::KeyJ, "jJ"),
+        (Key::KeyK, "kK"),
+        (Key::KeyL, "lL"),
+        (Key::SemiColon, ";:"),
+        (Key::Quote, "'\""),
+        (Key::KeyZ, "zZ"),
+        (Key::KeyX, "xX"),
+        (Key::KeyC, "cC"),
+        (Key::KeyV, "vV"),
+        (Key::KeyB, "bB"),
+        (Key::KeyN, "nN"),
+        (Key::KeyM, "mM"),
+        (Key::Comma, ",<"),
+        (Key::Dot, ".>"),
+        (Key::Slash, "/?"),
+    ],
+};
+
+/// German QWERTZ, as in XKB's `de`.
+const DE_KEYS: &[(Key, &str)] = &[
+    (Key::BackQuote, "\u{302}°′″"),
+    (Key::Num1, "1!¹¡"),
+    (Key::Num2, "2\"²⅛"),
+    (Key::Num3, "3§³£"),
+    (Key::Num4, "4$¼¤"),
+    (Key::Num5, "5%½⅜"),
+    (Key::Num6, "6&¬⅝"),
+    (Key::Num7, "7/{⅞"),
+    (Key::Num8, "8([™"),
+    (Key::Num9, "9)]±"),
+    (Key::Num0, "0=}°"),
+    (Key::Minus, "ß?\\¿"),
+    (Key::Equal, "\u{301}\u{300}\u{327}\u{328}"),
+    (Key::KeyQ, "qQ@Ω"),
+    (Key::KeyW, "wWłŁ"),
+    (Key::KeyE, "eE€€"),
+    (Key::KeyR, "rR¶®"),
+    (Key::KeyT, "tTŧŦ"),
+    (Key::KeyY, "zZ←¥"),
+    (Key::KeyU, "uU↓↑"),
+    (Key::KeyI, "iI→ı"),
+    (Key::KeyO, "oOøØ"),
+    (Key::KeyP, "pPþÞ"),
+    (Key::LeftBracket, "üÜ\u{308}\u{30a}"),
+    (Key::RightBracket, "+*~¯"),
+    (Key::BackSlash, "#'’\u{306}"),
+    (Key::KeyA, "aAæÆ"),
+    (Key::KeyS, "sSſẞ"),
+    (Key::KeyD, "dDðÐ"),
+    (Key::KeyF, "fFđª"),
+    (Key::KeyG, "gGŋŊ"),
+    (Key::KeyH, "hHħĦ"),
+    (Key::KeyJ, "jJ\u{323}\u{307}"),
+    (Key::KeyK, "kKĸ&"),
+    (Key::KeyL, "lLłŁ"),
+    (Key::SemiColon, "öÖ\u{30b}\u{323}"),
+    (Key::Quote, "äÄ\u{302}\u{30c}"),
+    (Key::IntlBackslash, "<>|"),
+    (Key::KeyZ, "yY»›"),
+    (Key::KeyX, "xX«‹"),
+    (Key::KeyC, "cC¢©"),
+    (Key::KeyV, "vV„‚"),
+    (Key::KeyB, "bB“‘"),
+    (Key::KeyN, "nN”’"),
+    (Key::KeyM, "mMµº"),
+    (Key::Comma, ",;·×"),
+    (Key::Dot, ".:…÷"),
+    (Key::Slash, "-_–—"),
+];
+
+/// French AZERTY, as in XKB's `fr`.
+const FR_KEYS: &[(Key, &str)] = &[
+    (Key::BackQuote, "²"),
+    (Key::Num1, "&1"),
+    (Key::Num2, "é2~"),
+    (Key::Num3, "\"3#"),
+    (Key::Num4, "'4{"),
+    (Key::Num5, "(5["),
+    (Key::Num6, "-6|"),
+    (Key::Num7, "è7`"),
+    (Key::Num8, "_8\\"),
+    (Key::Num9, "ç9^"),
+    (Key::Num0, "à0@"),
+    (Key::Minus, ")°]"),
+    (Key::Equal, "=+}"),
+    (Key::KeyQ, "aAæÆ"),
+    (Key::KeyW, "zZ«"),
+    (Key::KeyE, "eE€"),
+    (Key::KeyR, "rR"),
+    (Key::KeyT, "tT"),
+    (Key::KeyY, "yY"),
+    (Key::KeyU, "uU"),
+    (Key::KeyI, "iI"),
+    (Key::KeyO, "oOœŒ"),
+    (Key::KeyP, "pP"),
+    (Key::LeftBracket, "\u{302}\u{308}"),
+    (Key::RightBracket, "$£¤"),
+    (Key::BackSlash, "*µ"),
+    (Key::KeyA, "qQ"),
+    (Key::KeyS, "sS"),
+    (Key::KeyD, "dD"),
+    (Key::KeyF, "fF"),
+    (Key::KeyG, "gG"),
+    (Key::KeyH, "hH"),
+    (Key::KeyJ, "jJ"),
+    (Key::KeyK, "kK"),
+    (Key::KeyL, "lL"),
+    (Key::SemiColon, "mM"),
+    (Key::Quote, "ù%"),
+    (Key::IntlBackslash, "<>"),
+    (Key::KeyZ, "wW"),
+    (Key::KeyX, "xX"),
+    (Key::KeyC, "cC"),
+    (Key::KeyV, "vV"),
+    (Key::KeyB, "bB"),
+    (Key::KeyN, "nN"),
+    (Key::KeyM, ",?"),
+    (Key::Comma, ";."),
+    (Key::Dot, ":/"),
+    (Key::Slash, "!§"),
+];
+
+static DE: Layout = Layout {
+    name: "de",
+    keys: DE_KEYS,
+    dead_keys: true,
+};
+
+static DE_NODEADKEYS: Layout = Layout {
+    name: "de(nodeadkeys)",
+    keys: DE_KEYS,
+    dead_keys: false,
+};
+
+static FR: Layout = Layout {
+    name: "fr",
+    keys: FR_KEYS,
+    dead_keys: true,
+};
+
+static FR_NODEADKEYS: Layout = Layout {
+    name: "fr(nodeadkeys)",
+    keys: FR_KEYS,
+    dead_keys: false,
+};
+
+pub static LAYOUTS: [&Layout; 5] = [&US, &DE, &DE_NODEADKEYS, &FR, &FR_NODEADKEYS];
+
+/// Letters a dead key puts its accent on: the accent, the plain letters and the same letters
+/// with the accent.
+const COMPOSED: &[(char, &str, &str)] = &[
+    ('\u{301}', "aeiouyAEIOUY", "áéíóúýÁÉÍÓÚÝ"),
+    ('\u{300}', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    ('\u{302}', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    ('\u{308}', "aeiouyAEIOU", "äëïöüÿÄËÏÖÜ"),
+    ('\u{303}', "anoANO", "ãñõÃÑÕ"),
+    ('\u{327}', "cC", "çÇ"),
+    ('\u{30a}', "aA", "åÅ"),
+];
+
+/// Accents a dead key types on its own when followed by space.
+const SPACING: &[(char, char)] = &[
+    ('\u{301}', '´'),
+    ('\u{300}', '`'),
+    ('\u{302}', '^'),
+    ('\u{308}', '¨'),
+    ('\u{303}', '~'),
+    ('\u{327}', '¸'),
+];
+
+/// One press of a key, with the modifiers that pick its level.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stroke {
+    pub key: Key,
+    pub shift: bool,
+    pub altgr: bool,
+}
+
+impl Layout {
+    /// The key that types `c` as it is, on the lowest level that has it.
+    fn stroke(&self, c: char) -> Option<Stroke> {
+        let plain = |key| Stroke {
+            key,
+            shift: false,
+            altgr: false,
+        };
+        match c {
+            ' ' => return Some(plain(Key::Space)),
+            '\n' => return Some(plain(Key::Return)),
+            '\t' => return Some(plain(Key::Tab)),
+            _ => {}
+        }
+
+        (0..4).find_map(|level| {
+            self.keys
+                .iter()
+                .find(|(_, chars)| chars.chars().nth(level).map(|k| self.typed(k)) == Some(c))
+                .map(|(key, _)| Stroke {
+                    key: *key,
+                    shift: level % 2 == 1,
+                    altgr: level >= 2,
+                })
+        })
+    }
+
+    /// What a key on one level of the table types, accents instead of dead keys without them.
+    fn typed(&self, k: char) -> char {
+        match SPACING.iter().find(|(accent, _)| *accent == k) {
+            Some((_, spacing)) if !self.dead_keys => *spacing,
+            _ => k,
+        }
+    }
+
+    /// What to press to type `c`, going through a dead key for accents the layout has no key
+    /// for. `None` if it can't be typed at all.
+    pub fn strokes(&self, c: char) -> Option<Vec<Stroke>> {
+        if let Some(stroke) = self.stroke(c) {
+            return Some(vec![stroke]);
+        }
+        if !self.dead_keys {
+            return None;
+        }
+
+        for (accent, plain, composed) in COMPOSED {
+            if let Some(i) = composed.chars().position(|x| x == c) {
+                let letter = plain.chars().nth(i)?;
+                return Some(vec![self.stroke(*accent)?, self.stroke(letter)?]);
+            }
+        }
+
+        let (accent, _) = SPACING.iter().find(|(_, s)| *s == c)?;
+        Some(vec![self.stroke(*accent)?, self.stroke(' ')?])
+    }
+}
+
+pub fn find(name: &str) -> Option<&'static Layout> {
+    LAYOUTS.into_iter().find(|l| l.name == name)
+}
+
+/// The first layout and its variant in the output of `setxkbmap -query`, e.g. `de` and
+/// `nodeadkeys` for `layout: de,us` and `variant: nodeadkeys,`.
+fn parse_query(stdout: &str) -> Option<(String, String)> {
+    let field = |name: &str| {
+        stdout
+            .lines()
+            .find_map(|l| l.strip_prefix(name))
+            .and_then(|v| v.trim().split(',').next())
+            .map(|v| v.trim().to_string())
+    };
+    Some((field("layout:")?, field("variant:").unwrap_or_default()))
+}
+
+fn query_xkb() -> Option<(String, String)> {
+    let output = Command::new("setxkbmap").arg("-query").output().ok()?;
+    parse_query(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn query_env() -> Option<(String, String)> {
+    let first = |var: &str| {
+        let value = std::env::var(var).ok()?;
+        Some(value.split(',').next()?.trim().to_string())
+    };
+    Some((
+        first("XKB_DEFAULT_LAYOUT")?,
+        first("XKB_DEFAULT_VARIANT").unwrap_or_default(),
+    ))
+}
+
+/// The table for an XKB layout and variant, or why there's none.
+fn resolve(layout: &str, variant: &str) -> Result<&'static Layout, String> {
+    let name = match variant {
+        "" | "basic" => layout.to_string(),
+        variant => format!("{}({})", layout, variant),
+    };
+    find(&name).ok_or_else(|| {
+        let names: Vec<&str> = LAYOUTS.iter().map(|l| l.name).collect();
+        format!(
+            "no table for keyboard layout `{}`, only for {}",
+            name,
+            names.join(", ")
+        )
+    })
+}
+
+/// The layout the system types with, read once. Under Wayland `setxkbmap` only sees
+/// XWayland's layout, so `XKB_DEFAULT_LAYOUT` goes first there.
+fn detect() -> &'static Layout {
+    static DETECTED: OnceLock<&'static Layout> = OnceLock::new();
+
+    DETECTED.get_or_init(|| {
+        let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some_and(|d| !d.is_empty());
+        let queried = match wayland {
+            true => query_env().or_else(query_xkb),
+            false => query_xkb().or_else(query_env),
+        };
+
+        let Some((layout, variant)) = queried else {
+            warn!(
+                "couldn't read the keyboard layout, typing as `us`, set --keyboard-layout if that's wrong"
+            );
+            return &US;
+        };
+        match resolve(&layout, &variant) {
+            Ok(layout) => {
+                info!("keyboard layout: {}", layout.name);
+                layout
+            }
+            Err(e) => {
+                warn!(
+                    "{}, typing as `us` so text may come out wrong, set --keyboard-layout to pick another",
+                    e
+                );
+                &US
+            }
+        }
+    })
+}
+
+/// Index into `LAYOUTS` of the `--keyboard-layout` in effect, or `usize::MAX` to detect it.
+static CURRENT: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Sets the layout text is typed with, `None` for the one the system uses.
+pub fn set_current(layout: Option<&'static Layout>) {
+    let index = layout
+        .and_then(|layout| LAYOUTS.iter().position(|l| std::ptr::eq(*l, layout)))
+        .unwrap_or(usize::MAX);
+    CURRENT.store(index, Ordering::Relaxed);
+}
+
+pub fn current() -> &'static Layout {
+    match LAYOUTS.get(CURRENT.load(Ordering::Relaxed)) {
+        Some(layout) => layout,
+        None => detect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(key: Key) -> Stroke {
+        Stroke {
+            key,
+            shift: false,
+            altgr: false,
+        }
+    }
+
+    fn strokes(layout: &str, c: char) -> Option<Vec<Stroke>> {
+        find(layout).unwrap().strokes(c)
+    }
+
+    #[test]
+    fn letters_sit_where_the_layout_has_them() {
+        assert_eq!(strokes("us", 'y'), Some(vec![plain(Key::KeyY)]));
+        assert_eq!(strokes("de", 'y'), Some(vec![plain(Key::KeyZ)]));
+        assert_eq!(strokes("de", 'z'), Some(vec![plain(Key::KeyY)]));
+        assert_eq!(strokes("fr", 'a'), Some(vec![plain(Key::KeyQ)]));
+        assert_eq!(strokes("fr", 'é'), Some(vec![plain(Key::Num2)]));
+        assert!(strokes("fr", '1').unwrap()[0].shift);
+    }
+
+    #[test]
+    fn altgr_levels() {
+        let euro = Stroke {
+            key: Key::KeyE,
+            shift: false,
+            altgr: true,
+        };
+        assert_eq!(strokes("de", '€'), Some(vec![euro]));
+        assert_eq!(strokes("fr", '€'), Some(vec![euro]));
+        assert_eq!(strokes("us", '€'), None);
+    }
+
+    #[test]
+    fn dead_keys_compose_accents() {
+        assert_eq!(
+            strokes("de", 'é'),
+            Some(vec![plain(Key::Equal), plain(Key::KeyE)])
+        );
+        assert_eq!(
+            strokes("fr", 'ê'),
+            Some(vec![plain(Key::LeftBracket), plain(Key::KeyE)])
+        );
+        assert_eq!(
+            strokes("de", '^'),
+            Some(vec![plain(Key::BackQuote), plain(Key::Space)])
+        );
+    }
+
+    #[test]
+    fn nodeadkeys_types_accents_straight_away() {
+        assert_eq!(
+            strokes("de(nodeadkeys)", '^'),
+            Some(vec![plain(Key::BackQuote)])
+        );
+        assert_eq!(strokes("de(nodeadkeys)", 'é'), None);
+        assert_eq!(strokes("de(nodeadkeys)", 'y'), Some(vec![plain(Key::KeyZ)]));
+    }
+
+    #[test]
+    fn query_output() {
+        let stdout =
+            "rules:      evdev\nmodel:      pc105\nlayout:     de,us\nvariant:    nodeadkeys,\n";
+        assert_eq!(
+            parse_query(stdout),
+            Some(("de".to_string(), "nodeadkeys".to_string()))
+        );
+        assert_eq!(
+            parse_query("layout:     fr\n"),
+            Some(("fr".to_string(), String::new()))
+        );
+        assert_eq!(parse_query(""), None);
+    }
+
+    #[test]
+    fn variants_resolve_or_explain() {
+        assert_eq!(resolve("de", ""), Ok(&DE));
+        assert_eq!(resolve("de", "nodeadkeys"), Ok(&DE_NODEADKEYS));
+        assert_eq!(resolve("us", "basic"), Ok(&US));
+        assert!(resolve("de", "neo").unwrap_err().contains("`de(neo)`"));
+        assert!(resolve("gb", "").is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Whitespace,
+    Ident(String),
+    Str(String),
+    Delim(char),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+    /// byte offset one past the last character of the token
+    pub end: usize,
+}
+
+impl Token {
+    pub fn is_delim(&self, c: char) -> bool {
+        self.kind == TokenKind::Delim(c)
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Whitespace => write!(f, "whitespace"),
+            TokenKind::Ident(s) => write!(f, "`{}`", s),
+            TokenKind::Str(s) => write!(f, "string {:?}", s),
+            TokenKind::Delim(c) => write!(f, "`{}`", c),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub file: Option<String>,
+    pub span: Span,
+    pub message: String,
+    source_line: String,
+}
+
+impl Diagnostic {
+    pub fn new(src: &str, span: Span, message: impl Into<String>) -> Self {
+        let source_line = src.lines().nth(span.line - 1).unwrap_or("").to_string();
+
+        Self {
+            file: None,
+            span,
+            message: message.into(),
+            source_line,
+        }
+    }
+
+    pub fn in_file(mut self, file: &str) -> Self {
+        self.file = Some(file.to_string());
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+        }
+        writeln!(
+            f,
+            "{}:{}: {}",
+            self.span.line, self.span.column, self.message
+        )?;
+        writeln!(f, "   | {}", self.source_line)?;
+        write!(f, "   | {}^", " ".repeat(self.span.column - 1))
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_' || !c.is_ascii()
+}
+
+struct Lexer<'a> {
+    src: &'a str,
+    chars: Vec<(usize, char)>,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn peek(&self, ahead: usize) -> Option<char> {
+        self.chars.get(self.pos + ahead).map(|(_, c)| *c)
+    }
+
+    fn span(&self) -> Span {
+        Span {
+            offset: self.offset(),
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn offset(&self) -> usize {
+        self.chars
+            .get(self.pos)
+            .map(|(o, _)| *o)
+            .unwrap_or(self.src.len())
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek(0)?;
+        self.pos += 1;
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(c)
+    }
+
+    fn error(&self, span: Span, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(self.src, span, message)
+    }
+
+    fn escape(&mut self, start: Span) -> Result<char, Diagnostic> {
+        match self.bump() {
+            Some('n') => Ok('\n'),
+            Some('t') => Ok('\t'),
+            Some(c) => Ok(c),
+            None => Err(self.error(start, "unterminated escape sequence")),
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, Diagnostic> {
+        let start = self.span();
+
+        let Some(c) = self.peek(0) else {
+            return Ok(None);
+        };
+
+        let kind = match c {
+            c if c.is_whitespace() => {
+                while self.peek(0).is_some_and(char::is_whitespace) {
+                    self.bump();
+                }
+                TokenKind::Whitespace
+            }
+
+            // comments are dropped entirely
+            '/' if self.peek(1) == Some('*') => {
+                self.bump();
+                self.bump();
+                loop {
+                    match self.bump() {
+                        Some('*') if self.peek(0) == Some('/') => {
+                            self.bump();
+                            break;
+                        }
+                        Some(_) => {}
+                        None => return Err(self.error(start, "unterminated comment")),
+                    }
+                }
+                return self.next_token();
+            }
+
+            '"' | '\'' => {
+                let quote = c;
+                self.bump();
+
+                let mut s = String::new();
+                loop {
+                    let here = self.span();
+                    match self.bump() {
+                        Some(c) if c == quote => break,
+                        Some('\\') => s.push(self.escape(here)?),
+                        Some('\n') | None => {
+                            return Err(self.error(start, "unterminated string"));
+                        }
+                        Some(c) => s.push(c),
+                    }
+                }
+                TokenKind::Str(s)
+            }
+
+            c if is_ident_char(c) || c == '\\' => {
+                let mut s = String::new();
+                loop {
+                    let here = self.span();
+                    match self.peek(0) {
+                        Some('\\') => {
+                            self.bump();
+                            s.push(self.escape(here)?);
+                        }
+                        Some(c) if is_ident_char(c) => {
+                            self.bump();
+                            s.push(c);
+                        }
+                        _ => break,
+                    }
+                }
+                TokenKind::Ident(s)
+            }
+
+            _ => {
+                self.bump();
+                TokenKind::Delim(c)
+            }
+        };
+
+        Ok(Some(Token {
+            kind,
+            span: start,
+            end: self.offset(),
+        }))
+    }
+}
+
+pub fn tokenize(src: &str) -> Result<Vec<Token>, Diagnostic> {
+    let mut lexer = Lexer {
+        src,
+        chars: src.char_indices().collect(),
+        pos: 0,
+        line: 1,
+        column: 1,
+    };
+
+    let mut tokens = vec![];
+    while let Some(token) = lexer.next_token()? {
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TokenKind::*;
+
+    fn kinds(src: &str) -> Vec<TokenKind> {
+        tokenize(src).unwrap().into_iter().map(|t| t.kind).collect()
+    }
+
+    fn error(src: &str) -> (usize, usize, String) {
+        let e = tokenize(src).unwrap_err();
+        (e.span.line, e.span.column, e.message)
+    }
+
+    fn str(s: &str) -> TokenKind {
+        Str(s.to_string())
+    }
+
+    #[test]
+    fn strings_and_escapes() {
+        assert_eq!(
+            kinds(r#""a\"b\n\tc" 'it\'s'"#),
+            [str("a\"b\n\tc"), Whitespace, str("it's")]
+        );
+        assert_eq!(
+            kinds(r#"'say "hi"' "it's""#),
+            [str("say \"hi\""), Whitespace, str("it's")]
+        );
+        assert_eq!(kinds(r#""""#), [str("")]);
+        assert_eq!(kinds(r"org\.gnome"), [Ident("org.gnome".to_string())]);
+    }
+
+    #[test]
+    fn slashes_at_either_end() {
+        assert_eq!(kinds("/"), [Delim('/')]);
+        assert_eq!(
+            kinds("/a/"),
+            [Delim('/'), Ident("a".to_string()), Delim('/')]
+        );
+        assert_eq!(
+            kinds("a /* b */ /"),
+            [Ident("a".to_string()), Whitespace, Whitespace, Delim('/')]
+        );
+        assert_eq!(kinds("/**/"), []);
+        assert_eq!(error("/*"), (1, 1, "unterminated comment".to_string()));
+        assert_eq!(error("a\n/*/"), (2, 1, "unterminated comment".to_string()));
+    }
+
+    #[test]
+    fn errors_point_at_what_is_unterminated() {
+        assert_eq!(error(r#"a: "x"#), (1, 4, "unterminated string".to_string()));
+        assert_eq!(error("a: 'x\n'"), (1, 4, "unterminated string".to_string()));
+        // columns count characters, not bytes
+        assert_eq!(
+            error("\n  é: \"\\"),
+            (2, 7, "unterminated escape sequence".to_string())
+        );
+        assert_eq!(
+            error("a\\"),
+            (1, 2, "unterminated escape sequence".to_string())
+        );
+    }
+
+    #[test]
+    fn spans_are_offsets_lines_and_columns() {
+        let tokens = tokenize("é\n  \"x\"").unwrap();
+        let last = tokens.last().unwrap();
+        assert_eq!(
+            last.span,
+            Span {
+                offset: 5,
+                line: 2,
+                column: 3
+            }
+        );
+        assert_eq!(last.end, 8);
+    }
+
+    #[test]
+    fn diagnostics_show_the_line_and_a_caret() {
+        let e = tokenize("a: 1;\nb: \"x").unwrap_err().in_file("f.css");
+        assert_eq!(
+            e.to_string(),
+            "f.css:2:4: unterminated string\n   | b: \"x\n   |    ^"
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "error" => Some(Level::Error),
+            "warn" | "warning" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::Level::Error) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::Level::Warn) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::Level::Info) {
+            println!($($arg)*);
+        }
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::Level::Debug) {
+            println!($($arg)*);
+        }
+    };
+}
@@ -0,0 +1,211 @@
+use std::{
+    sync::mpsc::{Receiver, RecvTimeoutError, Sender, TryRecvError, channel},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    keys::{modifier_key, named_key},
+    settings::parse_duration,
+    simulation::{MOUSE_ACTIONS, send_action},
+};
+
+/// One step of a `"ctrl+a, 50ms, ctrl+c"` sequence.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// keys or a click, sent like a binding of its own
+    Send(String),
+    Wait(Duration),
+}
+
+/// `50ms` or `1.5s`, or why it's a bad delay. A bare number is a key here rather than a delay.
+fn wait(step: &str) -> Option<Result<Duration, String>> {
+    let starts_with_digit = step.chars().next().is_some_and(|c| c.is_ascii_digit());
+    if !starts_with_digit || !step.ends_with('s') {
+        return None;
+    }
+    Some(parse_duration(step))
+}
+
+/// Whether `step` is something `send_action` can send without typing it out as text.
+fn is_action(step: &str) -> bool {
+    if step != "mouse" && MOUSE_ACTIONS.contains(&step) {
+        return true;
+    }
+
+    step.split('+').all(|token| {
+        let name = token.to_lowercase();
+        modifier_key(&name).is_some() || named_key(&name).is_some() || token.chars().count() == 1
+    })
+}
+
+/// The steps of a sequence value, or why one of them is wrong. `None` if it isn't a sequence,
+/// which takes at least two steps, a delay or keys among them that aren't just text, and no
+/// plugin or function like `repeat(...)`.
+pub fn parse_sequence(val: &str) -> Option<Result<Vec<Step>, String>> {
+    if val.starts_with('@') || val.contains('(') {
+        return None;
+    }
+
+    let parts: Vec<&str> = val.split(',').map(str::trim).collect();
+    // `ctrl+,` is a combo that ends in a comma
+    if parts.len() < 2 || parts.iter().any(|p| p.is_empty()) {
+        return None;
+    }
+    // `Hello, world` is text with a comma in it
+    if !parts
+        .iter()
+        .any(|p| wait(p).is_some() || (is_action(p) && p.chars().count() > 1))
+    {
+        return None;
+    }
+
+    let steps = parts
+        .into_iter()
+        .map(|part| match wait(part) {
+            Some(d) => d.map(Step::Wait),
+            None if is_action(part) => Ok(Step::Send(part.to_string())),
+            None => Err(format!(
+                "unknown step `{}` in `{}`, expected keys like `ctrl+c`, a click or a delay like `50ms`",
+                part, val
+            )),
+        })
+        .collect();
+    Some(steps)
+}
+
+/// Sends the steps one by one, until they run out or `cancel` says to stop.
+fn play(name: &str, steps: &[Step], cancel: &Receiver<()>) {
+    for step in steps {
+        let cancelled = match step {
+            Step::Send(action) => {
+                // each step is sent whole, so a cancelled sequence leaves no keys down
+                let cancelled = !matches!(cancel.try_recv(), Err(TryRecvError::Empty));
+                if !cancelled {
+                    send_action(action);
+                }
+                cancelled
+            }
+            Step::Wait(d) => !matches!(cancel.recv_timeout(*d), Err(RecvTimeoutError::Timeout)),
+        };
+
+        if cancelled {
+            debug!("sequence cancelled: {}", name);
+            return;
+        }
+    }
+    debug!("sequence done: {}", name);
+}
+
+struct Running {
+    binding: String,
+    cancel: Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl Running {
+    /// Stops the sequence at its next step.
+    fn cancel(self) {
+        // it may have just finished on its own
+        let _ = self.cancel.send(());
+    }
+}
+
+/// Plays sequences on threads of their own, so their delays never hold up gamepad input.
+#[derive(Default)]
+pub struct Macros {
+    running: Vec<Running>,
+}
+
+impl Macros {
+    /// Starts playing `steps` for `binding`, or cancels them if they're still playing.
+    pub fn run_or_cancel(&mut self, binding: &str, steps: Vec<Step>) {
+        self.running.retain(|r| !r.thread.is_finished());
+
+        if let Some(pos) = self.running.iter().position(|r| r.binding == binding) {
+            self.running.remove(pos).cancel();
+            return;
+        }
+
+        debug!("sequence started: {}", binding);
+        let (cancel, cancelled) = channel();
+        let name = binding.to_string();
+        let thread = thread::spawn(move || play(&name, &steps, &cancelled));
+        self.running.push(Running {
+            binding: binding.to_string(),
+            cancel,
+            thread,
+        });
+    }
+
+    pub fn cancel_all(&mut self) {
+        for r in self.running.drain(..) {
+            r.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send(action: &str) -> Step {
+        Step::Send(action.to_string())
+    }
+
+    #[test]
+    fn sequences_of_keys_clicks_and_delays() {
+        assert_eq!(
+            parse_sequence("ctrl+a, 50ms, ctrl+c,click , 1.5s, x"),
+            Some(Ok(vec![
+                send("ctrl+a"),
+                Step::Wait(Duration::from_millis(50)),
+                send("ctrl+c"),
+                send("click"),
+                Step::Wait(Duration::from_millis(1500)),
+                send("x"),
+            ]))
+        );
+        assert_eq!(
+            parse_sequence("enter, 5"),
+            Some(Ok(vec![send("enter"), send("5")]))
+        );
+    }
+
+    #[test]
+    fn single_actions_are_not_sequences() {
+        for val in ["ctrl+c", "ctrl+,", ", a", "@plugin, x", r#"repeat("a, b")"#] {
+            assert_eq!(parse_sequence(val), None, "{}", val);
+        }
+    }
+
+    #[test]
+    fn text_with_commas_is_typed() {
+        for val in ["Hello, world", "a, b", "1, 2, 3", "yes, no, maybe"] {
+            assert_eq!(parse_sequence(val), None, "{}", val);
+        }
+    }
+
+    #[test]
+    fn unknown_steps_are_errors() {
+        assert_eq!(
+            parse_sequence("ctrl+a, hello"),
+            Some(Err(
+                "unknown step `hello` in `ctrl+a, hello`, expected keys like `ctrl+c`, a click or a delay like `50ms`"
+                    .to_string()
+            ))
+        );
+        assert!(parse_sequence("Hello, 50ms").unwrap().is_err());
+    }
+
+    #[test]
+    fn bad_delays_are_errors() {
+        assert_eq!(
+            parse_sequence("x, 1e300s"),
+            Some(Err(
+                "expected a duration like `800ms` or `1.5s`, found `1e300s`".to_string()
+            ))
+        );
+        assert!(parse_sequence("ctrl+c, 5xs").unwrap().is_err());
+    }
+}
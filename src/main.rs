@@ -1,20 +1,69 @@
+#[macro_use]
+mod logging;
+
+mod check;
+mod chord;
+mod cli;
+mod clipboard;
 mod config;
+mod gestures;
+mod keys;
+mod layers;
+mod layout;
+mod lexer;
+mod macros;
+mod parser;
 mod plugins;
+mod pointer;
+mod process;
+mod reload;
+mod repeat;
+mod settings;
 mod simulation;
+mod sink;
+mod sticks;
+mod toggles;
+mod triggers;
+#[cfg(target_os = "linux")]
+mod uinput;
 use active_win_pos_rs::get_active_window;
-use gilrs::{Button, Event, Gilrs};
-use std::fs::read_to_string;
+use gilrs::{Axis, Button, Event, Gilrs};
+use std::{
+    collections::HashMap,
+    fs::read_to_string,
+    time::{Duration, Instant},
+};
 
-use config::Config;
+use cli::{Command, USAGE, parse_args};
+use config::{Config, Window};
+use logging::Level;
 
 use crate::{
+    gestures::{Gestures, has_gestures},
+    layers::{LayerAction, Layers},
+    macros::{Macros, parse_sequence},
     plugins::{run_every_service, run_plugin_and_send_combo},
+    pointer::Pointer,
+    process::foreground_process,
+    reload::watch_config,
+    repeat::{Repeater, parse_repeat, parse_turbo},
+    settings::Settings,
+    settings::StickRole,
     simulation::{
-        move_mouse_delta, scroll_mouse, send_combo, send_left_click, send_middle_click,
-        send_right_click,
+        HeldKeys, MOUSE_ACTIONS, move_mouse_delta, parse_hold, scroll_mouse, send_action,
+        send_combo, send_left_click, send_middle_click, send_right_click, set_sink,
     },
+    sticks::{Sticks, role_keys, role_owner, uses_stick},
+    toggles::{Toggles, parse_toggle},
+    triggers::{Edge, Triggers},
 };
 
+/// Every name `get_gamepad_key` can produce for a real button.
+pub const GAMEPAD_KEYS: [&str; 17] = [
+    "a", "b", "x", "y", "up", "down", "left", "right", "lb", "lt", "rb", "rt", "ls", "rs", "start",
+    "select", "guide",
+];
+
 fn get_gamepad_key(button: Button) -> &'static str {
     use gilrs::Button::*;
 
@@ -51,48 +100,227 @@ fn get_gamepad_key(button: Button) -> &'static str {
     }
 }
 
-fn modulate_stick_sensitivity_for_mouse(lx: f32, ly: f32) -> (f64, f64) {
-    let deadzone = 0.;
+/// The button an analog trigger's axis stands for, named like the button gilrs reports for
+/// the same trigger when it has a mapping.
+fn trigger_axis_key(axis: Axis) -> &'static str {
+    match axis {
+        Axis::LeftZ => get_gamepad_key(Button::LeftTrigger2),
+        Axis::RightZ => get_gamepad_key(Button::RightTrigger2),
+        _ => "unknown",
+    }
+}
 
-    let gamma = 3.;
+/// The stick an axis belongs to, and both of its axes.
+fn stick_axes(axis: Axis) -> Option<(&'static str, Axis, Axis)> {
+    match axis {
+        Axis::LeftStickX | Axis::LeftStickY => Some(("ls", Axis::LeftStickX, Axis::LeftStickY)),
+        Axis::RightStickX | Axis::RightStickY => Some(("rs", Axis::RightStickX, Axis::RightStickY)),
+        _ => None,
+    }
+}
 
-    let max_sensitivity = 1.0;
+/// What fired bindings keep going after they fire.
+#[derive(Default)]
+struct Effects {
+    held_keys: HeldKeys,
+    layers: Layers,
+    repeater: Repeater,
+    toggles: Toggles,
+    macros: Macros,
+}
 
-    let mut dx = 0.0f64;
-    let mut dy = 0.0f64;
+/// Runs a binding that fires once: a plain one on release, or one picked by `Gestures`, so
+/// mouse buttons get a whole click. `down` is the gamepad button still held when it fired,
+/// which `hold(...)`, `repeat(...)`, `turbo(...)` and `layer(...)` values hold on to.
+fn fire_binding(
+    name: &str,
+    val: &str,
+    cfg: &Config,
+    plugins_dir: &str,
+    effects: &mut Effects,
+    down: Option<&str>,
+) {
+    debug!("match: {} {:?}", name, val);
+
+    // plain bindings with these values already acted when their button went down
+    let on_press = MOUSE_ACTIONS.contains(&val)
+        || parse_hold(val).is_some()
+        || parse_repeat(val).is_some()
+        || parse_turbo(val).is_some()
+        || parse_toggle(val).is_some()
+        || matches!(LayerAction::parse(val), Some(LayerAction::Momentary(_)));
+    if !name.contains(':') && on_press {
+        return;
+    }
+
+    if let Some(button) = down
+        && press_binding(val, button, effects)
+    {
+        return;
+    }
 
-    if lx.abs() > deadzone {
-        let norm = (lx.abs() - deadzone) / (1.0 - deadzone);
-        let curved = norm.powf(gamma);
-        dx = lx.signum() as f64 * curved as f64 * max_sensitivity;
+    // with no button left to hold, these fire once
+    if let Some(combo) = parse_hold(val) {
+        send_combo(combo);
+        return;
     }
 
-    if ly.abs() > deadzone {
-        let norm = (ly.abs() - deadzone) / (1.0 - deadzone);
-        let curved = norm.powf(gamma);
-        dy = -ly.signum() as f64 * curved as f64 * max_sensitivity;
+    if let Some(repeat) = parse_repeat(val).or_else(|| parse_turbo(val)) {
+        match repeat {
+            Ok(repeat) => send_action(repeat.action),
+            Err(e) => warn!("{}", e),
+        }
+        return;
     }
 
-    (dx, dy)
+    if let Some(action) = parse_toggle(val) {
+        effects.toggles.toggle(action, &mut effects.held_keys);
+        return;
+    }
+
+    if let Some(action) = LayerAction::parse(val) {
+        effects.layers.run(action);
+        return;
+    }
+
+    // a sequence plays on its own thread, firing it again while it plays cancels it
+    if let Some(steps) = parse_sequence(val) {
+        match steps {
+            Ok(steps) => effects.macros.run_or_cancel(name, steps),
+            Err(e) => warn!("{}", e),
+        }
+        return;
+    }
+
+    match val {
+        "mouse" => {}
+        _ if val.starts_with('@') => run_plugin_and_send_combo(val, cfg, plugins_dir),
+        _ => send_action(val),
+    }
+}
+
+/// Starts a value that goes on for as long as `button` is held, whatever gets pressed
+/// meanwhile: `hold(...)`, `repeat(...)`, `turbo(...)` or a held `layer(...)`, and `toggle(...)`
+/// which doesn't wait for the release. `false` if the value is none of these.
+fn press_binding(val: &str, button: &str, effects: &mut Effects) -> bool {
+    if let Some(combo) = parse_hold(val) {
+        effects.held_keys.press(button, combo);
+    } else if let Some(repeat) = parse_repeat(val) {
+        match repeat {
+            Ok(repeat) => effects.repeater.start(button, &repeat, Instant::now()),
+            Err(e) => warn!("{}", e),
+        }
+    } else if let Some(turbo) = parse_turbo(val) {
+        match turbo {
+            Ok(turbo) => effects.repeater.turbo(button, &turbo, Instant::now()),
+            Err(e) => warn!("{}", e),
+        }
+    } else if let Some(action) = parse_toggle(val) {
+        effects.toggles.toggle(action, &mut effects.held_keys);
+    } else if let Some(LayerAction::Momentary(layer)) = LayerAction::parse(val) {
+        effects.layers.hold(button, layer);
+    } else {
+        return false;
+    }
+    true
+}
+
+/// Whether the held buttons make a chord bound to `mouse`.
+fn mouse_mode(held: &[String], query: &HashMap<String, String>, settings: &Settings) -> bool {
+    query
+        .get(&chord::resolve(held, query, settings.chord_order))
+        .is_some_and(|val| val == "mouse")
+}
+
+/// Reads the settings out of a query, and hands the ones typing needs over to it.
+fn load_settings(query: &HashMap<String, String>) -> Settings {
+    let settings = Settings::from_query(query);
+    layout::set_current(settings.keyboard_layout);
+    settings
 }
 
-struct MouseAccumulator {
-    x: f32,
-    y: f32,
-    sx: f32,
-    sy: f32,
+const WINDOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 250 Hz, how often the sticks are sampled while they drive the mouse
+const TICK: Duration = Duration::from_millis(4);
+
+/// Upper bound on how long the loop sleeps, so config reloads apply without gamepad input.
+const IDLE_WAKEUP: Duration = Duration::from_millis(250);
+
+fn focused_window(with_process: bool) -> Option<Window> {
+    let win = get_active_window().ok()?;
+
+    let process = if with_process {
+        foreground_process(win.process_id as u32).unwrap_or_default()
+    } else {
+        String::new()
+    };
+
+    Some(Window {
+        class: win.app_name,
+        title: win.title,
+        process,
+    })
 }
 
 fn main() {
-    let config_content = read_to_string("./config.css").expect("failed to read config");
-    let cfg = Config::load_from(&config_content);
+    let opts = match parse_args(std::env::args().skip(1)) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
 
-    cfg.print();
+    logging::set_level(opts.log_level);
 
-    println!("===");
-    println!("{:#?}", cfg.query(".does_not_exist".to_string()));
+    let config_path = opts.config.as_str();
+    let plugins_dir = opts.plugins_dir.as_str();
 
-    run_every_service("./plugins".to_string(), &cfg);
+    match &opts.command {
+        Command::Help => {
+            println!("{}", USAGE);
+            return;
+        }
+        Command::Check => {
+            let ok = check::check(config_path, plugins_dir);
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        Command::Explain(window) => {
+            let ok = check::explain(config_path, window);
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        Command::Keys => {
+            keys::print_reference();
+            return;
+        }
+        Command::Run => {}
+    }
+
+    set_sink(sink::open(opts.backend, opts.dry_run));
+
+    let config_content = match read_to_string(config_path) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("error reading {}: {}", config_path, e);
+            std::process::exit(1);
+        }
+    };
+    let mut cfg = match Config::load_from(&config_content) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            error!("error parsing config: {}", e.in_file(config_path));
+            std::process::exit(1);
+        }
+    };
+
+    if logging::enabled(Level::Debug) {
+        cfg.print();
+    }
+
+    run_every_service(plugins_dir.to_string(), &cfg);
+
+    let config_updates = watch_config(config_path);
 
     let mut gampad_key_stack: Vec<String> = vec![];
 
@@ -102,138 +330,352 @@ fn main() {
 
     // Iterate over all connected gamepads
     for (_id, gamepad) in gilrs.gamepads() {
-        println!("{} is {:?}", gamepad.name(), gamepad.power_info());
+        info!("{} is {:?}", gamepad.name(), gamepad.power_info());
     }
 
     let mut active_gamepad = None;
 
-    let mut mouse_acc = MouseAccumulator {
-        x: 0.,
-        y: 0.,
-        sx: 0.,
-        sy: 0.,
-    };
+    let mut pointer = Pointer::default();
+    let mut gestures = Gestures::default();
+    let mut effects = Effects::default();
+    let mut triggers = Triggers::default();
+    let mut sticks = Sticks::default();
+    let mut stick_keys = Sticks::default();
 
-    let mut active_app_query = cfg.query("uninitialized".to_string());
+    let mut active_app_query = cfg.query(&Window::default(), &effects.layers.active());
+    let mut settings = load_settings(&active_app_query);
 
-    let mut prev_window = get_active_window().expect("failed to get active window");
-    let mut current_window = get_active_window().expect("failed to get active window");
+    let mut current_window = Window::default();
 
-    loop {
-        // Examine new events
-        while let Some(Event { id, event, .. }) = gilrs.next_event() {
-            active_gamepad = Some(id);
+    let mut uses_process = cfg.uses_attribute("process");
+    let mut last_window_poll = Instant::now();
+    let mut next_tick = Instant::now();
 
-            if let Ok(win) = get_active_window()
-                && win.window_id != prev_window.window_id
-            {
-                prev_window = current_window;
-                current_window = win;
-                active_app_query = cfg.query(format!(".{}", current_window.app_name));
+    loop {
+        // swap in a saved config, whatever is held right now stays held
+        if let Some(new_cfg) = config_updates.try_iter().last() {
+            cfg = new_cfg;
+            uses_process = cfg.uses_attribute("process");
+            if uses_process {
+                current_window = focused_window(uses_process).unwrap_or(current_window);
             }
+            active_app_query = cfg.query(&current_window, &effects.layers.active());
+            settings = load_settings(&active_app_query);
+            is_mouse_mode = mouse_mode(&gampad_key_stack, &active_app_query, &settings);
+        }
 
-            match event {
-                gilrs::EventType::ButtonPressed(button, _) => {
-                    gampad_key_stack.push(get_gamepad_key(button).to_string());
+        // sleep until the next gamepad event, waking up early only for work that's due
+        let now = Instant::now();
+        let mut wake_at = now + IDLE_WAKEUP;
+        if is_mouse_mode {
+            wake_at = wake_at.min(next_tick);
+        }
+        if uses_process {
+            wake_at = wake_at.min(last_window_poll + WINDOW_POLL_INTERVAL);
+        }
+        if let Some(deadline) = gestures.next_deadline(&active_app_query, &settings) {
+            wake_at = wake_at.min(deadline);
+        }
+        if let Some(deadline) = effects.repeater.next_deadline() {
+            wake_at = wake_at.min(deadline);
+        }
 
-                    if let Some(val) = active_app_query.get(&gampad_key_stack.join("-")) {
-                        match val.as_str() {
-                            "mouse" => {
-                                is_mouse_mode = true;
-                            }
-                            "click" | "leftclick" => {
-                                send_left_click(false);
-                            }
-                            "rightclick" => {
-                                send_right_click(false);
-                            }
-                            "middleclick" => {
-                                send_middle_click(false);
+        if let Some(Event { id, event, .. }) =
+            gilrs.next_event_blocking(Some(wake_at.saturating_duration_since(now)))
+        {
+            active_gamepad = Some(id);
+
+            // analog buttons are pressed at the configured thresholds, the rest when gilrs says so
+            let edges = match event {
+                gilrs::EventType::ButtonPressed(button, _) => {
+                    triggers.digital(get_gamepad_key(button), true)
+                }
+                gilrs::EventType::ButtonReleased(button, _) => {
+                    triggers.digital(get_gamepad_key(button), false)
+                }
+                gilrs::EventType::ButtonChanged(button, value, _) => {
+                    triggers.changed(get_gamepad_key(button), value, &settings)
+                }
+                // triggers without a button mapping show up as axes resting at -1
+                gilrs::EventType::AxisChanged(axis @ (Axis::LeftZ | Axis::RightZ), value, _) => {
+                    triggers.changed(trigger_axis_key(axis), (value + 1.) / 2., &settings)
+                }
+                // a stick is only buttons while something is bound to its directions, and not
+                // while it drives the mouse
+                gilrs::EventType::AxisChanged(axis, _, _) => match stick_axes(axis) {
+                    Some((stick, x, y)) => {
+                        let gamepad = gilrs.gamepad(id);
+                        let pos = (gamepad.value(x), gamepad.value(y));
+
+                        // in mouse mode, `arrows` and `wasd` sticks hold keys
+                        let role = settings.stick_role(stick);
+                        let keyed =
+                            is_mouse_mode && matches!(role, StickRole::Arrows | StickRole::Wasd);
+                        let mut role_edges = stick_keys.update(stick, pos, keyed, &settings);
+                        // press the new direction first, so keys it shares with the old one stay down
+                        role_edges.sort_by_key(|(_, edge)| *edge != Edge::Press);
+                        for (direction, edge) in role_edges {
+                            match (edge, role_keys(role, direction)) {
+                                (Edge::Press, Some(keys)) => {
+                                    effects.held_keys.press(&role_owner(direction), &keys)
+                                }
+                                _ => effects.held_keys.release(&role_owner(direction)),
                             }
-                            _ => {}
                         }
-                    } else {
-                        is_mouse_mode = false;
+
+                        let enabled = !is_mouse_mode && uses_stick(stick, &active_app_query);
+                        sticks.update(stick, pos, enabled, &settings)
                     }
+                    None => vec![],
+                },
+                // no release events will follow, so let go of everything now
+                gilrs::EventType::Disconnected => {
+                    info!("gamepad disconnected, releasing held buttons");
+                    match active_app_query
+                        .get(&chord::resolve(
+                            &gampad_key_stack,
+                            &active_app_query,
+                            settings.chord_order,
+                        ))
+                        .map(|v| v.as_str())
+                    {
+                        Some("click" | "leftclick") => send_left_click(true),
+                        Some("rightclick") => send_right_click(true),
+                        Some("middleclick") => send_middle_click(true),
+                        _ => {}
+                    }
+                    effects.toggles.release_all(&mut effects.held_keys);
+                    effects.held_keys.release_all();
+                    effects.layers.release_all();
+                    effects.repeater.release_all();
+                    effects.macros.cancel_all();
+                    gampad_key_stack.clear();
+                    gestures = Gestures::default();
+                    is_mouse_mode = false;
+                    active_gamepad = None;
+                    triggers = Triggers::default();
+                    sticks = Sticks::default();
+                    stick_keys = Sticks::default();
+                    vec![]
                 }
-                gilrs::EventType::ButtonReleased(button, _) => {
-                    if let Some(val) = active_app_query.get(&gampad_key_stack.join("-")) {
-                        match val.as_str() {
-                            "click" | "leftclick" => {
-                                send_left_click(true);
-                            }
-                            "rightclick" => {
-                                send_right_click(true);
-                            }
-                            "middleclick" => {
-                                send_middle_click(true);
+                _ => vec![],
+            };
+
+            // re-query when focus moves or the focused window renames itself, e.g. a browser tab switch
+            if !edges.is_empty() {
+                if let Some(window) = focused_window(uses_process)
+                    && window != current_window
+                {
+                    active_app_query = cfg.query(&window, &effects.layers.active());
+                    settings = load_settings(&active_app_query);
+                    current_window = window;
+                }
+                last_window_poll = Instant::now();
+            }
+
+            for (key, edge) in edges {
+                match edge {
+                    Edge::Press => {
+                        // like a keyboard, a new key stops the last one repeating
+                        effects.repeater.stop();
+                        effects.layers.pressed();
+                        gampad_key_stack.push(key.to_string());
+                        let chord = chord::resolve(
+                            &gampad_key_stack,
+                            &active_app_query,
+                            settings.chord_order,
+                        );
+
+                        for fired in gestures.press(&chord, key, Instant::now(), &active_app_query)
+                        {
+                            fire_binding(
+                                &fired.name,
+                                &fired.value,
+                                &cfg,
+                                plugins_dir,
+                                &mut effects,
+                                fired.button.as_deref(),
+                            );
+                        }
+
+                        match active_app_query.get(&chord).map(String::as_str) {
+                            Some("mouse") => is_mouse_mode = true,
+                            Some("click" | "leftclick") => send_left_click(false),
+                            Some("rightclick") => send_right_click(false),
+                            Some("middleclick") => send_middle_click(false),
+                            Some(val) => {
+                                press_binding(val, key, &mut effects);
                             }
-                            _ => {}
+                            None => is_mouse_mode = false,
                         }
 
-                        println!("match: {:?}", val);
-                        if val.starts_with("@") {
-                            let _ = run_plugin_and_send_combo(val, &cfg);
-                        } else {
-                            send_combo(val);
+                        // like a shift key, a button holding a layer on isn't part of the chords
+                        // pressed under it, the buttons held with it still are
+                        if effects.layers.holding(key) {
+                            gampad_key_stack.retain(|b| b != key);
                         }
                     }
+                    Edge::Full => {
+                        // like any gesture, only for the button that completed the chord
+                        if gampad_key_stack.last().is_some_and(|b| b == key) {
+                            for fired in gestures.full(&active_app_query) {
+                                fire_binding(
+                                    &fired.name,
+                                    &fired.value,
+                                    &cfg,
+                                    plugins_dir,
+                                    &mut effects,
+                                    fired.button.as_deref(),
+                                );
+                            }
+                        }
+                    }
+                    Edge::Release => {
+                        let chord = chord::resolve(
+                            &gampad_key_stack,
+                            &active_app_query,
+                            settings.chord_order,
+                        );
+
+                        // buttons that were taken out of the chord end nothing but what they held
+                        let pos = gampad_key_stack.iter().position(|x| x == key);
+
+                        if pos.is_some() {
+                            match active_app_query.get(&chord).map(|v| v.as_str()) {
+                                Some("click" | "leftclick") => send_left_click(true),
+                                Some("rightclick") => send_right_click(true),
+                                Some("middleclick") => send_middle_click(true),
+                                _ => {}
+                            }
 
-                    let button_string = get_gamepad_key(button).to_string();
-                    if let Some(pos) = gampad_key_stack.iter().position(|x| *x == button_string) {
-                        gampad_key_stack.remove(pos);
-                    };
+                            // with gesture bindings on the chord, the plain one is only its tap
+                            if !has_gestures(&chord, &active_app_query)
+                                && let Some(val) = active_app_query.get(&chord)
+                            {
+                                fire_binding(&chord, val, &cfg, plugins_dir, &mut effects, None);
+                            }
+                        }
+
+                        effects.held_keys.release(key);
+                        effects.layers.release(key);
+                        effects.repeater.release(key);
+
+                        if let Some(pos) = pos {
+                            for fired in
+                                gestures.release(Instant::now(), &active_app_query, &settings)
+                            {
+                                fire_binding(
+                                    &fired.name,
+                                    &fired.value,
+                                    &cfg,
+                                    plugins_dir,
+                                    &mut effects,
+                                    fired.button.as_deref(),
+                                );
+                            }
 
-                    if let Some(val) = active_app_query.get(&gampad_key_stack.join("-")) {
-                        if val == "mouse" {
-                            is_mouse_mode = true;
-                        } else {
-                            is_mouse_mode = false;
+                            gampad_key_stack.remove(pos);
                         }
-                    } else {
-                        is_mouse_mode = false;
+                        if gampad_key_stack.is_empty() {
+                            effects.layers.chord_ended();
+                        }
+
+                        is_mouse_mode = mouse_mode(&gampad_key_stack, &active_app_query, &settings);
                     }
                 }
-                _ => {}
             }
         }
 
-        if let Some(gamepad) = active_gamepad.map(|id| gilrs.gamepad(id)) {
-            if !is_mouse_mode {
-                continue;
-            };
-
-            mouse_acc.x += gamepad.value(gilrs::Axis::LeftStickX);
-            mouse_acc.y += gamepad.value(gilrs::Axis::LeftStickY);
+        for fired in gestures.poll(Instant::now(), &active_app_query, &settings) {
+            fire_binding(
+                &fired.name,
+                &fired.value,
+                &cfg,
+                plugins_dir,
+                &mut effects,
+                fired.button.as_deref(),
+            );
+        }
+        effects.repeater.poll(Instant::now());
+        gampad_key_stack.retain(|b| !effects.layers.holding(b));
+
+        // a layer went on or off, so the held buttons may mean something else now
+        if effects.layers.take_changed() {
+            debug!("layers: {:?}", effects.layers.active());
+            active_app_query = cfg.query(&current_window, &effects.layers.active());
+            settings = load_settings(&active_app_query);
+            is_mouse_mode = mouse_mode(&gampad_key_stack, &active_app_query, &settings);
+        }
 
-            let mut m_coords = (0., 0.);
-            if mouse_acc.x.abs() > 1. {
-                m_coords.0 = modulate_stick_sensitivity_for_mouse(mouse_acc.x.into(), 0.).0;
-                mouse_acc.x = 0.;
-            }
-            if mouse_acc.y.abs() > 1. {
-                m_coords.1 = modulate_stick_sensitivity_for_mouse(0., mouse_acc.y.into()).1;
-                mouse_acc.y = 0.;
+        // what runs inside a terminal changes without any gamepad input, so keep looking
+        if uses_process && last_window_poll.elapsed() >= WINDOW_POLL_INTERVAL {
+            if let Some(window) = focused_window(uses_process)
+                && window != current_window
+            {
+                active_app_query = cfg.query(&window, &effects.layers.active());
+                settings = load_settings(&active_app_query);
+                current_window = window;
             }
+            last_window_poll = Instant::now();
+        }
 
-            move_mouse_delta(m_coords.0, m_coords.1);
-
-            let scroll_sensitivity = 0.001 * 9.;
-
-            mouse_acc.sx += gamepad.value(gilrs::Axis::RightStickX) * scroll_sensitivity;
-            mouse_acc.sy += gamepad.value(gilrs::Axis::RightStickY) * scroll_sensitivity;
+        // analog input is sampled at a fixed rate, and only while something consumes it
+        let now = Instant::now();
+        if !is_mouse_mode {
+            pointer.reset();
+            for (direction, _) in stick_keys.release_all() {
+                effects.held_keys.release(&role_owner(direction));
+            }
+            continue;
+        }
+        if now < next_tick {
+            continue;
+        }
+        next_tick += TICK;
+        if next_tick < now {
+            next_tick = now + TICK;
+        }
 
-            let mut m_scroll_coords = (0., 0.);
-            if mouse_acc.sx.abs() > 1. {
-                m_scroll_coords.0 = modulate_stick_sensitivity_for_mouse(mouse_acc.sx.into(), 0.).0;
-                mouse_acc.sx = 0.;
+        if let Some(gamepad) = active_gamepad.map(|id| gilrs.gamepad(id)) {
+            let ((dx, dy), (sx, sy)) = pointer.step(
+                now,
+                (
+                    gamepad.value(Axis::LeftStickX),
+                    gamepad.value(Axis::LeftStickY),
+                ),
+                (
+                    gamepad.value(Axis::RightStickX),
+                    gamepad.value(Axis::RightStickY),
+                ),
+                &settings,
+            );
+
+            if dx != 0. || dy != 0. {
+                move_mouse_delta(dx, dy);
             }
-            if mouse_acc.sy.abs() > 1. {
-                m_scroll_coords.1 = modulate_stick_sensitivity_for_mouse(0., mouse_acc.sy.into()).1;
-                mouse_acc.sy = 0.;
+            if sx != 0 || sy != 0 {
+                scroll_mouse(sx, sy);
             }
-
-            scroll_mouse((m_scroll_coords.0) as i64, -(m_scroll_coords.1) as i64);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trigger_axes_are_named_like_their_buttons() {
+        assert_eq!(trigger_axis_key(Axis::LeftZ), "lb");
+        assert_eq!(trigger_axis_key(Axis::RightZ), "rb");
+        assert_eq!(
+            trigger_axis_key(Axis::LeftZ),
+            get_gamepad_key(Button::LeftTrigger2)
+        );
+        assert_eq!(
+            trigger_axis_key(Axis::RightZ),
+            get_gamepad_key(Button::RightTrigger2)
+        );
+        assert_eq!(trigger_axis_key(Axis::LeftStickX), "unknown");
+    }
+}
@@ -0,0 +1,842 @@
+use std::fmt;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::lexer::{Diagnostic, Span, Token, TokenKind, tokenize};
+
+#[derive(Debug, Clone)]
+pub struct Stylesheet {
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub selectors: Vec<Selector>,
+    pub declarations: Vec<Declaration>,
+    /// set for rules inside `@layer nav { ... }`
+    pub layer: Option<String>,
+    /// set for the `*` rule a run of bare declarations in a layer stands for
+    pub bare: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Selector {
+    pub components: Vec<Component>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Component {
+    Universal,
+    Id(String),
+    Class(String),
+    Attribute(Attribute),
+}
+
+#[derive(Debug, Clone)]
+pub enum AttrOp {
+    /// `[title]`
+    Exists,
+    /// `[title="..."]`
+    Equals,
+    /// `[title*="..."]`
+    Contains,
+    /// `[title^="..."]`
+    Prefix,
+    /// `[title$="..."]`
+    Suffix,
+    /// `[title~="..."]`, one of the whitespace separated words
+    Word,
+    /// `[title=~"..."]`, not in CSS, matches a regular expression
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone)]
+pub struct Attribute {
+    pub name: String,
+    pub op: AttrOp,
+    pub value: String,
+    /// set by a trailing `i`, like `[title*="youtube" i]`
+    pub case_insensitive: bool,
+    pub span: Span,
+}
+
+impl Attribute {
+    pub fn matches(&self, actual: &str) -> bool {
+        let (actual, value) = if self.case_insensitive {
+            (actual.to_lowercase(), self.value.to_lowercase())
+        } else {
+            (actual.to_string(), self.value.clone())
+        };
+
+        match &self.op {
+            AttrOp::Exists => !actual.is_empty(),
+            AttrOp::Equals => actual == value,
+            AttrOp::Contains => actual.contains(&value),
+            AttrOp::Prefix => actual.starts_with(&value),
+            AttrOp::Suffix => actual.ends_with(&value),
+            AttrOp::Word => actual.split_whitespace().any(|w| w == value),
+            // the regex was built with the flag already applied
+            AttrOp::Regex(re) => re.is_match(&actual),
+        }
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self.op {
+            AttrOp::Exists => return write!(f, "[{}]", self.name),
+            AttrOp::Equals => "=",
+            AttrOp::Contains => "*=",
+            AttrOp::Prefix => "^=",
+            AttrOp::Suffix => "$=",
+            AttrOp::Word => "~=",
+            AttrOp::Regex(_) => "=~",
+        };
+        let flag = if self.case_insensitive { " i" } else { "" };
+        write!(f, "[{}{}{:?}{}]", self.name, op, self.value, flag)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Declaration {
+    pub name: String,
+    /// a lone quoted string is unescaped, anything else is kept as written
+    pub value: String,
+    pub important: bool,
+    pub span: Span,
+}
+
+/// `(ids, classes)`, compared the same way CSS compares specificity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Specificity(pub u32, pub u32);
+
+impl Selector {
+    pub fn id(&self) -> Option<&str> {
+        self.components.iter().find_map(|c| match c {
+            Component::Id(id) => Some(id.as_str()),
+            _ => None,
+        })
+    }
+
+    /// `.org.gnome.Nautilus` names the single window class `org.gnome.Nautilus`
+    pub fn class_name(&self) -> Option<String> {
+        let classes: Vec<&str> = self
+            .components
+            .iter()
+            .filter_map(|c| match c {
+                Component::Class(class) => Some(class.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        if classes.is_empty() {
+            None
+        } else {
+            Some(classes.join("."))
+        }
+    }
+
+    pub fn attributes(&self) -> impl Iterator<Item = &Attribute> {
+        self.components.iter().filter_map(|c| match c {
+            Component::Attribute(a) => Some(a),
+            _ => None,
+        })
+    }
+
+    pub fn specificity(&self) -> Specificity {
+        let ids = self
+            .components
+            .iter()
+            .filter(|c| matches!(c, Component::Id(_)))
+            .count() as u32;
+
+        let classes = self.class_name().is_some() as u32 + self.attributes().count() as u32;
+
+        Specificity(ids, classes)
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in &self.components {
+            match c {
+                Component::Universal => write!(f, "*")?,
+                Component::Id(s) => write!(f, "#{}", s)?,
+                Component::Class(s) => write!(f, ".{}", s)?,
+                Component::Attribute(a) => write!(f, "{}", a)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|t| t.kind == TokenKind::Whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn eof_span(&self) -> Span {
+        let mut span = Span {
+            offset: self.src.len(),
+            line: 1,
+            column: 1,
+        };
+        for c in self.src.chars() {
+            if c == '\n' {
+                span.line += 1;
+                span.column = 1;
+            } else {
+                span.column += 1;
+            }
+        }
+        span
+    }
+
+    fn unexpected(&self, expected: &str) -> Diagnostic {
+        match self.peek() {
+            Some(t) => Diagnostic::new(
+                self.src,
+                t.span,
+                format!("expected {}, found {}", expected, t.kind),
+            ),
+            None => Diagnostic::new(
+                self.src,
+                self.eof_span(),
+                format!("expected {}, found end of file", expected),
+            ),
+        }
+    }
+
+    fn expect_ident(&mut self, expected: &str) -> Result<String, Diagnostic> {
+        match self.peek().map(|t| &t.kind) {
+            Some(TokenKind::Ident(s)) => {
+                let s = s.clone();
+                self.pos += 1;
+                Ok(s)
+            }
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn stylesheet(&mut self) -> Result<Stylesheet, Diagnostic> {
+        let mut rules = vec![];
+
+        self.skip_whitespace();
+        while self.peek().is_some() {
+            if self.eat_delim('@') {
+                self.layer(&mut rules)?;
+            } else {
+                rules.push(self.rule()?);
+            }
+            self.skip_whitespace();
+        }
+
+        Ok(Stylesheet { rules })
+    }
+
+    /// `@layer nav { ... }` holds rules, or bare declarations which are short for `* { ... }`.
+    fn layer(&mut self, rules: &mut Vec<Rule>) -> Result<(), Diagnostic> {
+        let at = self.peek().map(|t| t.span).unwrap_or_default();
+        if self.expect_ident("`layer` after `@`")? != "layer" {
+            return Err(Diagnostic::new(
+                self.src,
+                at,
+                "unknown at-rule, only `@layer` is supported",
+            ));
+        }
+        self.skip_whitespace();
+        let name = self.expect_ident("a layer name")?;
+        self.skip_whitespace();
+        if !self.eat_delim('{') {
+            return Err(self.unexpected("`{` after layer name"));
+        }
+
+        // each run of bare declarations is a `*` rule where it stands, so source order still
+        // breaks cascade ties against the rules around it
+        let mut bare: Option<usize> = None;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(t) if t.is_delim('}') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(t) if t.is_delim(';') => {
+                    self.pos += 1;
+                }
+                // selectors never start with a name, so this is a declaration
+                Some(Token {
+                    kind: TokenKind::Ident(_),
+                    span,
+                    ..
+                }) => {
+                    let span = *span;
+                    let decl = self.declaration()?;
+                    let index = *bare.get_or_insert_with(|| {
+                        rules.push(Rule {
+                            selectors: vec![Selector {
+                                components: vec![Component::Universal],
+                                span,
+                            }],
+                            declarations: vec![],
+                            layer: Some(name.clone()),
+                            bare: true,
+                        });
+                        rules.len() - 1
+                    });
+                    rules[index].declarations.push(decl);
+                }
+                Some(_) => {
+                    let mut rule = self.rule()?;
+                    rule.layer = Some(name.clone());
+                    rules.push(rule);
+                    bare = None;
+                }
+                None => return Err(self.unexpected("`}`")),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn rule(&mut self) -> Result<Rule, Diagnostic> {
+        let mut selectors = vec![self.selector()?];
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(t) if t.is_delim(',') => {
+                    self.pos += 1;
+                    self.skip_whitespace();
+                    selectors.push(self.selector()?);
+                }
+                Some(t) if t.is_delim('{') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => return Err(self.unexpected("`,` or `{` after selector")),
+            }
+        }
+
+        let mut declarations = vec![];
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(t) if t.is_delim('}') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(t) if t.is_delim(';') => {
+                    self.pos += 1;
+                }
+                Some(_) => declarations.push(self.declaration()?),
+                None => return Err(self.unexpected("`}`")),
+            }
+        }
+
+        Ok(Rule {
+            selectors,
+            declarations,
+            layer: None,
+            bare: false,
+        })
+    }
+
+    fn selector(&mut self) -> Result<Selector, Diagnostic> {
+        let span = self.peek().map(|t| t.span).unwrap_or_default();
+        let mut components = vec![];
+
+        while let Some(t) = self.peek() {
+            match t.kind {
+                TokenKind::Delim('*') => {
+                    self.pos += 1;
+                    components.push(Component::Universal);
+                }
+                TokenKind::Delim('.') => {
+                    self.pos += 1;
+                    components.push(Component::Class(self.expect_ident("a class name")?));
+                }
+                TokenKind::Delim('#') => {
+                    self.pos += 1;
+                    components.push(Component::Id(self.expect_ident("an id")?));
+                }
+                TokenKind::Delim('[') => {
+                    self.pos += 1;
+                    components.push(Component::Attribute(self.attribute()?));
+                }
+                TokenKind::Delim(':') => {
+                    self.pos += 1;
+                    components.push(Component::Attribute(self.pseudo_class()?));
+                }
+                _ => break,
+            }
+        }
+
+        if components.is_empty() {
+            return Err(self.unexpected("a selector"));
+        }
+
+        Ok(Selector { components, span })
+    }
+
+    fn eat_delim(&mut self, c: char) -> bool {
+        if self.peek().is_some_and(|t| t.is_delim(c)) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn attribute(&mut self) -> Result<Attribute, Diagnostic> {
+        self.skip_whitespace();
+        let span = self.peek().map(|t| t.span).unwrap_or_default();
+        let name = self.expect_ident("an attribute name")?;
+        self.skip_whitespace();
+
+        if self.eat_delim(']') {
+            return Ok(Attribute {
+                name,
+                op: AttrOp::Exists,
+                value: String::new(),
+                case_insensitive: false,
+                span,
+            });
+        }
+
+        let op_span = self.peek().map(|t| t.span).unwrap_or_default();
+        let op = match self.peek().map(|t| &t.kind) {
+            Some(TokenKind::Delim('=')) => {
+                self.pos += 1;
+                if self.eat_delim('~') { "=~" } else { "=" }
+            }
+            Some(TokenKind::Delim(c @ ('*' | '^' | '$' | '~'))) => {
+                let op = match c {
+                    '*' => "*=",
+                    '^' => "^=",
+                    '$' => "$=",
+                    _ => "~=",
+                };
+                self.pos += 1;
+                if !self.eat_delim('=') {
+                    return Err(self.unexpected("`=`"));
+                }
+                op
+            }
+            _ => return Err(self.unexpected("`]` or an attribute operator")),
+        };
+        self.skip_whitespace();
+
+        let value = match self.peek().map(|t| &t.kind) {
+            Some(TokenKind::Str(s) | TokenKind::Ident(s)) => {
+                let s = s.clone();
+                self.pos += 1;
+                s
+            }
+            _ => return Err(self.unexpected("an attribute value")),
+        };
+        self.skip_whitespace();
+
+        let mut case_insensitive = false;
+        if let Some(TokenKind::Ident(flag)) = self.peek().map(|t| &t.kind)
+            && flag.eq_ignore_ascii_case("i")
+        {
+            case_insensitive = true;
+            self.pos += 1;
+            self.skip_whitespace();
+        }
+
+        if !self.eat_delim(']') {
+            return Err(self.unexpected("`]`"));
+        }
+
+        let op = match op {
+            "=" => AttrOp::Equals,
+            "*=" => AttrOp::Contains,
+            "^=" => AttrOp::Prefix,
+            "$=" => AttrOp::Suffix,
+            "~=" => AttrOp::Word,
+            _ => match RegexBuilder::new(&value)
+                .case_insensitive(case_insensitive)
+                .build()
+            {
+                Ok(re) => AttrOp::Regex(re),
+                Err(e) => {
+                    return Err(Diagnostic::new(
+                        self.src,
+                        op_span,
+                        format!("invalid regular expression: {}", e),
+                    ));
+                }
+            },
+        };
+
+        Ok(Attribute {
+            name,
+            op,
+            value,
+            case_insensitive,
+            span,
+        })
+    }
+
+    /// `:process(nvim)` is shorthand for `[process="nvim"]`
+    fn pseudo_class(&mut self) -> Result<Attribute, Diagnostic> {
+        let span = self.peek().map(|t| t.span).unwrap_or_default();
+        let name = self.expect_ident("a pseudo-class")?;
+
+        if name != "process" {
+            return Err(Diagnostic::new(
+                self.src,
+                span,
+                format!("unknown pseudo-class `:{}`", name),
+            ));
+        }
+
+        if !self.eat_delim('(') {
+            return Err(self.unexpected("`(`"));
+        }
+        self.skip_whitespace();
+
+        let value = match self.peek().map(|t| &t.kind) {
+            Some(TokenKind::Str(s) | TokenKind::Ident(s)) => {
+                let s = s.clone();
+                self.pos += 1;
+                s
+            }
+            _ => return Err(self.unexpected("a process name")),
+        };
+        self.skip_whitespace();
+
+        if !self.eat_delim(')') {
+            return Err(self.unexpected("`)`"));
+        }
+
+        Ok(Attribute {
+            name,
+            op: AttrOp::Equals,
+            value,
+            case_insensitive: false,
+            span,
+        })
+    }
+
+    /// The `:hold` in `a:hold: "x"` or the `:long(800ms)` in `a:long(800ms): "x"`.
+    /// Only counts if another `:` follows, so `a:hold;` is still `a` set to `hold`.
+    fn name_suffix(&mut self) -> Option<String> {
+        let start = self.pos;
+
+        let suffix = self.try_name_suffix();
+        if suffix.is_none() {
+            self.pos = start;
+        }
+        suffix
+    }
+
+    fn try_name_suffix(&mut self) -> Option<String> {
+        if !self.eat_delim(':') {
+            return None;
+        }
+        let Some(TokenKind::Ident(ident)) = self.peek().map(|t| &t.kind) else {
+            return None;
+        };
+        let mut suffix = format!(":{}", ident);
+        self.pos += 1;
+
+        if self.eat_delim('(') {
+            suffix.push('(');
+            loop {
+                let t = self.peek()?.clone();
+                self.pos += 1;
+                match t.kind {
+                    TokenKind::Delim(')') => break,
+                    TokenKind::Whitespace => {}
+                    _ => suffix.push_str(&self.src[t.span.offset..t.end]),
+                }
+            }
+            suffix.push(')');
+        }
+
+        self.skip_whitespace();
+        self.peek()?.is_delim(':').then_some(suffix)
+    }
+
+    fn declaration(&mut self) -> Result<Declaration, Diagnostic> {
+        let span = self.peek().map(|t| t.span).unwrap_or_default();
+        let mut name = self.expect_ident("a property name")?;
+        // `rb+lt`, a chord that can be pressed in any order
+        while self.eat_delim('+') {
+            name.push('+');
+            name.push_str(&self.expect_ident("a button name after `+`")?);
+        }
+        if let Some(suffix) = self.name_suffix() {
+            name.push_str(&suffix);
+        }
+
+        self.skip_whitespace();
+        match self.peek() {
+            Some(t) if t.is_delim(':') => self.pos += 1,
+            _ => return Err(self.unexpected("`:` after property name")),
+        }
+        self.skip_whitespace();
+
+        let first = self.pos;
+        let mut depth = 0usize;
+        while let Some(t) = self.peek() {
+            match t.kind {
+                TokenKind::Delim('(') | TokenKind::Delim('[') => depth += 1,
+                TokenKind::Delim(')') | TokenKind::Delim(']') if depth > 0 => depth -= 1,
+                TokenKind::Delim(';') | TokenKind::Delim('}') if depth == 0 => break,
+                TokenKind::Delim('{') => return Err(self.unexpected("`;` or `}`")),
+                _ => {}
+            }
+            self.pos += 1;
+        }
+
+        let mut value_tokens = trim_end(&self.tokens[first..self.pos]);
+
+        let mut important = false;
+        if let [rest @ .., last] = value_tokens
+            && last.kind == TokenKind::Ident("important".to_string())
+            && let [rest @ .., bang] = trim_end(rest)
+            && bang.is_delim('!')
+        {
+            important = true;
+            value_tokens = trim_end(rest);
+        }
+
+        let value = match value_tokens {
+            [] => return Err(self.unexpected("a value")),
+            [
+                Token {
+                    kind: TokenKind::Str(s),
+                    ..
+                },
+            ] => s.clone(),
+            [first, ..] => {
+                let end = value_tokens[value_tokens.len() - 1].end;
+                self.src[first.span.offset..end].to_string()
+            }
+        };
+
+        Ok(Declaration {
+            name,
+            value,
+            important,
+            span,
+        })
+    }
+}
+
+fn trim_end(mut tokens: &[Token]) -> &[Token] {
+    while let [rest @ .., last] = tokens
+        && last.kind == TokenKind::Whitespace
+    {
+        tokens = rest;
+    }
+    tokens
+}
+
+pub fn parse(src: &str) -> Result<Stylesheet, Diagnostic> {
+    let tokens = tokenize(src)?;
+    let mut parser = Parser {
+        src,
+        tokens,
+        pos: 0,
+    };
+
+    parser.stylesheet()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(src: &str) -> (usize, usize, String) {
+        let e = parse(src).unwrap_err();
+        (e.span.line, e.span.column, e.message)
+    }
+
+    #[test]
+    fn values_are_unescaped_only_when_quoted_alone() {
+        let sheet = parse(r#"* { a: "x\"y"; b: ctrl + c ; c: "x" "y"; }"#).unwrap();
+        let values: Vec<&str> = sheet.rules[0]
+            .declarations
+            .iter()
+            .map(|d| d.value.as_str())
+            .collect();
+        assert_eq!(values, ["x\"y", "ctrl + c", r#""x" "y""#]);
+    }
+
+    fn selector(src: &str) -> Selector {
+        parse(&format!("{} {{}}", src)).unwrap().rules[0].selectors[0].clone()
+    }
+
+    /// Whether the one attribute in `sel` accepts `actual`.
+    fn matching(sel: &str, actual: &str) -> bool {
+        selector(sel).attributes().next().unwrap().matches(actual)
+    }
+
+    #[test]
+    fn attribute_operators() {
+        assert!(matching("[title]", "x"));
+        assert!(!matching("[title]", ""));
+        assert!(matching(r#"[title="Inbox"]"#, "Inbox"));
+        assert!(!matching(r#"[title="Inbox"]"#, "Inbox - Mail"));
+        assert!(matching(r#"[title*="Tube"]"#, "YouTube"));
+        assert!(!matching(r#"[title*="tube"]"#, "YouTube"));
+        assert!(matching(r#"[title*="tube" i]"#, "YouTube"));
+        assert!(matching(r#"[title^="Git"]"#, "GitHub"));
+        assert!(!matching(r#"[title^="Hub"]"#, "GitHub"));
+        assert!(matching(r#"[title$=".rs"]"#, "main.rs"));
+        assert!(!matching(r#"[title$=".rs"]"#, "main.rs - vim"));
+        assert!(matching(r#"[title~="vim"]"#, "main.rs - vim"));
+        assert!(!matching(r#"[title~="vim"]"#, "nvim"));
+        assert!(matching("[process=nvim]", "nvim"));
+        assert!(matching(":process(nvim)", "nvim"));
+        assert!(!matching(":process(nvim)", "vim"));
+    }
+
+    #[test]
+    fn regex_attributes() {
+        assert!(matching(r#"[title=~"^[0-9]+ unread"]"#, "12 unread - Mail"));
+        assert!(!matching(
+            r#"[title=~"^[0-9]+ unread"]"#,
+            "Mail - 12 unread"
+        ));
+        assert!(!matching(r#"[title=~"^inbox"]"#, "Inbox (3)"));
+        assert!(matching(r#"[title=~"^inbox" i]"#, "Inbox (3)"));
+        // `=~` is one operator, `~=` another
+        assert!(matching(r#"[title =~ "a|b"]"#, "b"));
+        assert!(!matching(r#"[title~="a|b"]"#, "b"));
+        assert_eq!(
+            selector(r#"[title=~"a|b" i]"#).to_string(),
+            r#"[title=~"a|b" i]"#
+        );
+    }
+
+    #[test]
+    fn specificity_counts_ids_then_classes_and_attributes() {
+        assert_eq!(selector("*").specificity(), Specificity(0, 0));
+        assert_eq!(
+            selector(".org.gnome.Nautilus").specificity(),
+            Specificity(0, 1)
+        );
+        assert_eq!(
+            selector(".kitty[title]:process(nvim)").specificity(),
+            Specificity(0, 3)
+        );
+        assert_eq!(selector("#plugin.llm").specificity(), Specificity(1, 1));
+        assert!(selector("#plugin").specificity() > selector(".a[b][c]").specificity());
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        assert_eq!(
+            error(r#"* { b "x"; }"#),
+            (
+                1,
+                7,
+                "expected `:` after property name, found string \"x\"".to_string()
+            )
+        );
+        assert_eq!(
+            error("* { a: ; }"),
+            (1, 8, "expected a value, found `;`".to_string())
+        );
+        assert_eq!(
+            error(".x [title] {}"),
+            (
+                1,
+                4,
+                "expected `,` or `{` after selector, found `[`".to_string()
+            )
+        );
+        assert_eq!(
+            error("* {\n  a: \"b\" }\n}"),
+            (3, 1, "expected a selector, found `}`".to_string())
+        );
+    }
+
+    #[test]
+    fn errors_at_the_end_point_past_the_last_character() {
+        assert_eq!(
+            error("* {"),
+            (1, 4, "expected `}`, found end of file".to_string())
+        );
+        assert_eq!(
+            error("* {\n  a: \"é\""),
+            (2, 9, "expected `}`, found end of file".to_string())
+        );
+        assert_eq!(
+            error("/"),
+            (1, 1, "expected a selector, found `/`".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_pseudo_classes_and_at_rules_are_errors() {
+        assert_eq!(
+            error(":foo {}"),
+            (1, 2, "unknown pseudo-class `:foo`".to_string())
+        );
+        assert_eq!(
+            error("@media x {}"),
+            (
+                1,
+                2,
+                "unknown at-rule, only `@layer` is supported".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn bad_attributes_point_at_the_operator() {
+        assert_eq!(
+            error(r#"[title^"x"] {}"#),
+            (1, 8, "expected `=`, found string \"x\"".to_string())
+        );
+        let (line, column, message) = error(r#"[title=~"("] {}"#);
+        assert_eq!((line, column), (1, 7));
+        assert!(message.starts_with("invalid regular expression"));
+    }
+
+    #[test]
+    fn bare_declaration_runs_stay_in_place() {
+        let sheet = parse(r#"@layer nav { a: "x"; .app { a: "y"; } b: "z"; c: "w"; }"#).unwrap();
+        let rules: Vec<(String, Vec<&str>, bool)> = sheet
+            .rules
+            .iter()
+            .map(|r| {
+                let names = r.declarations.iter().map(|d| d.name.as_str()).collect();
+                (r.selectors[0].to_string(), names, r.bare)
+            })
+            .collect();
+
+        assert_eq!(
+            rules,
+            [
+                ("*".to_string(), vec!["a"], true),
+                (".app".to_string(), vec!["a"], false),
+                ("*".to_string(), vec!["b", "c"], true),
+            ]
+        );
+        assert!(
+            sheet
+                .rules
+                .iter()
+                .all(|r| r.layer.as_deref() == Some("nav"))
+        );
+    }
+}
@@ -5,7 +5,7 @@ use crate::{config::Config, simulation::send_combo};
 pub fn run_every_service(plugins_dir: String, cfg: &Config) {
     let read = read_dir(plugins_dir);
     if let Err(e) = read {
-        eprintln!("error opening the plugins directory: {}", e);
+        error!("error opening the plugins directory: {}", e);
         return;
     }
 
@@ -13,7 +13,7 @@ pub fn run_every_service(plugins_dir: String, cfg: &Config) {
 
     for entry in read {
         if let Err(e) = entry {
-            eprintln!("error reading entry: {}", e);
+            error!("error reading entry: {}", e);
             continue;
         }
 
@@ -21,23 +21,24 @@ pub fn run_every_service(plugins_dir: String, cfg: &Config) {
 
         let path = entry.path();
 
-        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-            if name.ends_with(".service") && path.is_file() {
-                println!("Starting {}", name);
-                let args = cfg.get_plugin_args(name.to_string());
-
-                thread::spawn(move || {
-                    let status = Command::new(&path).args(args).status();
-                    if let Err(e) = status {
-                        eprintln!("Error starting the service: {}", e);
-                    }
-                });
-            }
+        if let Some(name) = path.file_name().and_then(|n| n.to_str())
+            && name.ends_with(".service")
+            && path.is_file()
+        {
+            info!("Starting {}", name);
+            let args = cfg.get_plugin_args(name.to_string());
+
+            thread::spawn(move || {
+                let status = Command::new(&path).args(args).status();
+                if let Err(e) = status {
+                    error!("Error starting the service: {}", e);
+                }
+            });
         }
     }
 }
 
-pub fn run_plugin_and_send_combo(input: &String, cfg: &Config) {
+pub fn run_plugin_and_send_combo(input: &str, cfg: &Config, plugins_dir: &str) {
     // Split on whitespace. If you need shell-style quoting support, see note below.
     let mut parts = input.split("+").map(|e| e.to_string());
 
@@ -48,10 +49,11 @@ pub fn run_plugin_and_send_combo(input: &String, cfg: &Config) {
     let mut args: Vec<String> = parts.collect();
     args.extend_from_slice(&config_args);
 
-    // Build executable path: ./plugins/<binname>
-    let exe_path = Path::new("./plugins").join(binname);
+    // Build executable path: <plugins_dir>/<binname>
+    let exe_path = Path::new(plugins_dir).join(binname);
 
     if !exe_path.exists() {
+        warn!("plugin {} not found", exe_path.display());
         return;
     }
     let output;
@@ -62,7 +64,7 @@ pub fn run_plugin_and_send_combo(input: &String, cfg: &Config) {
         output = match Command::new("node").args(&args).output() {
             Ok(o) => o,
             Err(err) => {
-                eprintln!("Failed to run plugin {}: {}", exe_path.display(), err);
+                error!("Failed to run plugin {}: {}", exe_path.display(), err);
                 return;
             }
         };
@@ -73,7 +75,7 @@ pub fn run_plugin_and_send_combo(input: &String, cfg: &Config) {
         output = match Command::new("ts-node").args(&args).output() {
             Ok(o) => o,
             Err(err) => {
-                eprintln!("Failed to run plugin {}: {}", exe_path.display(), err);
+                error!("Failed to run plugin {}: {}", exe_path.display(), err);
                 return;
             }
         };
@@ -82,7 +84,7 @@ pub fn run_plugin_and_send_combo(input: &String, cfg: &Config) {
         output = match Command::new(&exe_path).args(&args).output() {
             Ok(o) => o,
             Err(err) => {
-                eprintln!("Failed to run plugin {}: {}", exe_path.display(), err);
+                error!("Failed to run plugin {}: {}", exe_path.display(), err);
                 return;
             }
         };
@@ -0,0 +1,282 @@
+use std::time::{Duration, Instant};
+
+use crate::settings::{DeadzoneShape, Settings, StickRole};
+
+/// Longest gap integrated in one step, so a stalled loop doesn't fling the cursor.
+const MAX_STEP: Duration = Duration::from_millis(50);
+
+/// `mouse-precision` speed as a fraction of `--mouse-speed`.
+const PRECISION_SCALE: f64 = 0.25;
+
+/// Shapes raw stick input into `-1..=1` per axis, with screen y pointing down.
+fn modulate_stick(lx: f32, ly: f32, settings: &Settings) -> (f64, f64) {
+    let (x, y) = (lx as f64, -ly as f64);
+    let deadzone = settings.deadzone;
+
+    let scale = |v: f64| {
+        if v <= deadzone {
+            return 0.;
+        }
+        settings.curve.apply((v - deadzone) / (1. - deadzone))
+    };
+
+    match settings.deadzone_shape {
+        DeadzoneShape::Axial => (x.signum() * scale(x.abs()), y.signum() * scale(y.abs())),
+        DeadzoneShape::Radial => {
+            let magnitude = x.hypot(y);
+            if magnitude == 0. {
+                return (0., 0.);
+            }
+            // diagonals can read past 1 on some pads
+            let scaled = scale(magnitude.min(1.)) / magnitude;
+            (x * scaled, y * scaled)
+        }
+    }
+}
+
+/// Splits off the whole part of `v`, leaving the fraction behind.
+fn take_whole(v: &mut f64) -> f64 {
+    let whole = v.trunc();
+    *v -= whole;
+    whole
+}
+
+/// Turns stick deflection into pointer and wheel motion at a speed that doesn't depend on how
+/// often it's stepped. Sub-pixel motion is carried over to the next step.
+#[derive(Default)]
+pub struct Pointer {
+    x: f64,
+    y: f64,
+    sx: f64,
+    sy: f64,
+    last: Option<Instant>,
+}
+
+impl Pointer {
+    /// Forgets the carry and timing, for when the sticks stop driving the pointer.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Returns whole pixels to move by and whole wheel steps to scroll by, each stick doing
+    /// what its role says.
+    pub fn step(
+        &mut self,
+        now: Instant,
+        left: (f32, f32),
+        right: (f32, f32),
+        settings: &Settings,
+    ) -> ((f64, f64), (i64, i64)) {
+        let dt = match self.last {
+            Some(last) => now.duration_since(last).min(MAX_STEP),
+            None => Duration::ZERO,
+        };
+        self.last = Some(now);
+        let dt = dt.as_secs_f64();
+
+        for ((x, y), role) in [(left, settings.left_stick), (right, settings.right_stick)] {
+            let (x, y) = modulate_stick(x, y, settings);
+            let mouse = settings.mouse_speed * dt;
+            let scroll = settings.scroll_speed * dt;
+
+            // wheel y grows upwards, screen y grows downwards
+            match role {
+                StickRole::Mouse => {
+                    self.x += x * mouse;
+                    self.y += y * mouse;
+                }
+                StickRole::MousePrecision => {
+                    self.x += x * mouse * PRECISION_SCALE;
+                    self.y += y * mouse * PRECISION_SCALE;
+                }
+                StickRole::Scroll => {
+                    self.sx += x * scroll;
+                    self.sy -= y * scroll;
+                }
+                StickRole::ScrollHorizontalOnly => self.sx += x * scroll,
+                StickRole::Arrows | StickRole::Wasd | StickRole::None => {}
+            }
+        }
+
+        (
+            (take_whole(&mut self.x), take_whole(&mut self.y)),
+            (
+                take_whole(&mut self.sx) as i64,
+                take_whole(&mut self.sy) as i64,
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Curve;
+
+    fn linear() -> Settings {
+        Settings {
+            curve: Curve::Linear,
+            ..Settings::default()
+        }
+    }
+
+    /// Steps `pointer` every `dt` for a second with the left stick at `left`, and returns the
+    /// total movement.
+    fn second(pointer: &mut Pointer, dt: Duration, left: (f32, f32)) -> (f64, f64) {
+        let settings = linear();
+        let start = Instant::now();
+        pointer.step(start, left, (0., 0.), &settings);
+
+        let mut total = (0., 0.);
+        let mut now = start;
+        while now < start + Duration::from_secs(1) {
+            now += dt;
+            let ((x, y), _) = pointer.step(now, left, (0., 0.), &settings);
+            assert_eq!((x.fract(), y.fract()), (0., 0.));
+            total = (total.0 + x, total.1 + y);
+        }
+        total
+    }
+
+    #[test]
+    fn movement_does_not_depend_on_frame_rate() {
+        for ms in [1, 4, 8, 10, 20, 25, 50] {
+            let (x, y) = second(
+                &mut Pointer::default(),
+                Duration::from_millis(ms),
+                (0.5, 0.),
+            );
+            assert!((x - 600.).abs() <= 1., "{}ms moved {}", ms, x);
+            assert_eq!(y, 0.);
+        }
+    }
+
+    #[test]
+    fn sub_pixel_motion_is_carried() {
+        // a quarter of a pixel per step
+        let mut pointer = Pointer::default();
+        let settings = Settings {
+            mouse_speed: 250.,
+            ..linear()
+        };
+        let start = Instant::now();
+        pointer.step(start, (1., 0.), (0., 0.), &settings);
+
+        let moved: Vec<f64> = (1..=8)
+            .map(|i| {
+                let now = start + Duration::from_millis(i);
+                pointer.step(now, (1., 0.), (0., 0.), &settings).0.0
+            })
+            .collect();
+        assert_eq!(moved.iter().sum::<f64>(), 2.);
+        assert!(moved.iter().all(|m| *m == 0. || *m == 1.));
+
+        // and dropped on reset
+        pointer.reset();
+        assert_eq!(
+            pointer.step(start, (1., 0.), (0., 0.), &settings).0,
+            (0., 0.)
+        );
+    }
+
+    #[test]
+    fn take_whole_keeps_the_fraction() {
+        let mut v = 2.75;
+        assert_eq!(take_whole(&mut v), 2.);
+        assert_eq!(v, 0.75);
+
+        let mut v = -1.5;
+        assert_eq!(take_whole(&mut v), -1.);
+        assert_eq!(v, -0.5);
+    }
+
+    #[test]
+    fn a_stalled_loop_moves_at_most_max_step() {
+        let settings = linear();
+        let mut pointer = Pointer::default();
+        let start = Instant::now();
+
+        // the first step has nothing to integrate
+        assert_eq!(
+            pointer.step(start, (1., 0.), (0., 0.), &settings).0,
+            (0., 0.)
+        );
+        let ((x, _), _) = pointer.step(
+            start + Duration::from_secs(1),
+            (1., 0.),
+            (0., 0.),
+            &settings,
+        );
+        assert_eq!(x, settings.mouse_speed * MAX_STEP.as_secs_f64());
+    }
+
+    #[test]
+    fn scroll_grows_upwards() {
+        let settings = linear();
+        let mut pointer = Pointer::default();
+        let start = Instant::now();
+
+        pointer.step(start, (0., 0.), (0., 1.), &settings);
+        let mut total = (0, 0);
+        for i in 1..=20 {
+            let (_, (sx, sy)) = pointer.step(start + MAX_STEP * i, (0., 0.), (0., 1.), &settings);
+            total = (total.0 + sx, total.1 + sy);
+        }
+        assert_eq!(total, (0, settings.scroll_speed as i64));
+    }
+
+    fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-6 && (a.1 - b.1).abs() < 1e-6
+    }
+
+    #[test]
+    fn axial_deadzone_cuts_each_axis() {
+        let settings = Settings {
+            deadzone: 0.2,
+            ..linear()
+        };
+        assert_eq!(modulate_stick(0.19, -0.19, &settings), (0., 0.));
+        assert!(close(modulate_stick(0.2, -0.2, &settings), (0., 0.)));
+        assert!(close(modulate_stick(0.6, 0., &settings), (0.5, 0.)));
+        assert!(close(modulate_stick(-1., 1., &settings), (-1., -1.)));
+
+        // a push that's mostly up stays straight up
+        assert!(close(modulate_stick(0.15, 0.9, &settings), (0., -0.875)));
+    }
+
+    #[test]
+    fn radial_deadzone_cuts_the_distance() {
+        let settings = Settings {
+            deadzone: 0.2,
+            deadzone_shape: DeadzoneShape::Radial,
+            ..linear()
+        };
+        assert_eq!(modulate_stick(0., 0., &settings), (0., 0.));
+        assert_eq!(modulate_stick(0.12, 0.16, &settings), (0., 0.));
+        assert!(close(modulate_stick(0.36, 0.48, &settings), (0.3, -0.4)));
+        assert!(close(modulate_stick(0., -1., &settings), (0., 1.)));
+
+        // diagonals past the rim are pulled back onto it
+        let h = std::f64::consts::FRAC_1_SQRT_2;
+        assert!(close(modulate_stick(1., 1., &settings), (h, -h)));
+    }
+
+    #[test]
+    fn gamma_is_finer_near_the_centre() {
+        let settings = Settings {
+            deadzone: 0.2,
+            curve: Curve::Gamma(2.),
+            ..Settings::default()
+        };
+        assert!(close(modulate_stick(0.2, 0., &settings), (0., 0.)));
+        assert!(close(modulate_stick(0.6, 0., &settings), (0.25, 0.)));
+        assert!(close(modulate_stick(-1., 0., &settings), (-1., 0.)));
+
+        let radial = Settings {
+            deadzone_shape: DeadzoneShape::Radial,
+            ..settings
+        };
+        assert!(close(modulate_stick(0., 0.6, &radial), (0., -0.25)));
+        assert!(close(modulate_stick(0., 1., &radial), (0., -1.)));
+    }
+}
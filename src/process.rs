@@ -0,0 +1,54 @@
+use std::fs::{read_dir, read_to_string};
+
+struct Stat {
+    pid: u32,
+    ppid: u32,
+    /// foreground process group of the controlling terminal, -1 when there is none
+    tpgid: i32,
+    starttime: u64,
+}
+
+fn read_stat(pid: u32) -> Option<Stat> {
+    let stat = read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+
+    // the command name can contain spaces and parens, so start after the last `)`
+    let rest = stat.get(stat.rfind(')')? + 2..)?;
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+
+    // `fields[0]` is field 3 in proc(5)
+    Some(Stat {
+        pid,
+        ppid: fields.get(1)?.parse().ok()?,
+        tpgid: fields.get(5)?.parse().ok()?,
+        starttime: fields.get(19)?.parse().ok()?,
+    })
+}
+
+fn children(pid: u32) -> Vec<Stat> {
+    let Ok(read) = read_dir("/proc") else {
+        return vec![];
+    };
+
+    read.flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(read_stat)
+        .filter(|stat| stat.ppid == pid)
+        .collect()
+}
+
+fn comm(pid: u32) -> Option<String> {
+    read_to_string(format!("/proc/{}/comm", pid))
+        .ok()
+        .map(|s| s.trim_end().to_string())
+}
+
+/// Name of the process in the foreground of a terminal emulator's tty, e.g. `nvim` or `bash`.
+/// With several tabs open, the most recently started foreground process wins.
+pub fn foreground_process(terminal_pid: u32) -> Option<String> {
+    children(terminal_pid)
+        .iter()
+        .filter(|shell| shell.tpgid > 0)
+        .filter_map(|shell| read_stat(shell.tpgid as u32))
+        .max_by_key(|leader| leader.starttime)
+        .and_then(|leader| comm(leader.pid))
+}
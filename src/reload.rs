@@ -0,0 +1,105 @@
+use std::{
+    fs::read_to_string,
+    path::Path,
+    sync::mpsc::{Receiver, Sender, channel},
+    thread,
+};
+
+use crate::config::Config;
+
+fn reload(path: &str, tx: &Sender<Config>) {
+    let src = match read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => {
+            warn!("error reading {}, keeping the previous config: {}", path, e);
+            return;
+        }
+    };
+
+    match Config::load_from(&src) {
+        Ok(cfg) => {
+            info!("reloaded {}", path);
+            let _ = tx.send(cfg);
+        }
+        Err(e) => {
+            warn!(
+                "error parsing config, keeping the previous one: {}",
+                e.in_file(path)
+            );
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn watch(path: String, tx: Sender<Config>) {
+    use inotify::{Inotify, WatchMask};
+
+    let file = Path::new(&path);
+    let dir = match file.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => Path::new("."),
+    };
+    let Some(name) = file.file_name().map(|n| n.to_os_string()) else {
+        return;
+    };
+
+    let mut inotify = match Inotify::init() {
+        Ok(i) => i,
+        Err(e) => {
+            error!("error watching {}, hot reload is off: {}", path, e);
+            return;
+        }
+    };
+
+    // watch the directory, editors often save by writing a new file and renaming it over the old one
+    if let Err(e) = inotify
+        .watches()
+        .add(dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO)
+    {
+        error!("error watching {}, hot reload is off: {}", path, e);
+        return;
+    }
+
+    let mut buffer = [0; 4096];
+    loop {
+        let events = match inotify.read_events_blocking(&mut buffer) {
+            Ok(events) => events,
+            Err(e) => {
+                error!("error watching {}, hot reload is off: {}", path, e);
+                return;
+            }
+        };
+
+        if events.into_iter().any(|e| e.name == Some(name.as_os_str())) {
+            reload(&path, &tx);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn watch(path: String, tx: Sender<Config>) {
+    use std::{fs::metadata, time::Duration};
+
+    let modified = || metadata(&path).and_then(|m| m.modified()).ok();
+
+    let mut last = modified();
+    loop {
+        thread::sleep(Duration::from_secs(1));
+
+        let now = modified();
+        if now != last {
+            last = now;
+            reload(&path, &tx);
+        }
+    }
+}
+
+/// Re-parses the config every time it's saved, a config that fails to parse is reported and skipped.
+pub fn watch_config(path: &str) -> Receiver<Config> {
+    let (tx, rx) = channel();
+    let path = path.to_string();
+
+    thread::spawn(move || watch(path, tx));
+
+    rx
+}
@@ -0,0 +1,339 @@
+use std::{
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
+
+use crate::{settings::parse_duration, simulation::send_action};
+
+/// How much shorter each repeat's wait gets, until it's down to the fastest rate.
+const ACCELERATION: f64 = 0.85;
+
+/// Turbo rates in hz, slow enough to be a crawl and fast enough for any game.
+const TURBO_RATES: RangeInclusive<f64> = 0.1..=1000.;
+
+const DEFAULT_DELAY: Duration = Duration::from_millis(300);
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(40);
+
+/// A `repeat("down", 300ms, 40ms)` value: the action, how long before it starts repeating and
+/// how often it repeats. A fourth time, e.g. `repeat("down", 300ms, 80ms, 20ms)`, makes it
+/// speed up to that rate for as long as the button is held. `turbo("click", 15hz)` is one
+/// with no delay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Repeat<'a> {
+    pub action: &'a str,
+    pub delay: Duration,
+    pub interval: Duration,
+    pub fastest: Duration,
+}
+
+/// The `repeat(...)` in a value, or why it's wrong. `None` if it isn't one.
+pub fn parse_repeat(val: &str) -> Option<Result<Repeat<'_>, String>> {
+    let inner = val.strip_prefix("repeat(")?.strip_suffix(')')?.trim();
+    Some(repeat_args(inner))
+}
+
+/// The `turbo(...)` in a value, or why it's wrong. `None` if it isn't one.
+pub fn parse_turbo(val: &str) -> Option<Result<Repeat<'_>, String>> {
+    let inner = val.strip_prefix("turbo(")?.strip_suffix(')')?.trim();
+    Some(turbo_args(inner))
+}
+
+/// Splits `"ctrl+,", 300ms` into the action and the arguments after it.
+fn split_action<'a>(func: &str, inner: &'a str) -> Result<(&'a str, Vec<&'a str>), String> {
+    // the action may be quoted, and then it can hold a `,` of its own
+    let (action, rest) = match inner.chars().next() {
+        Some(q @ ('"' | '\'')) => match inner[1..].split_once(q) {
+            Some((action, rest)) => (action, rest.trim_start()),
+            None => return Err(format!("unterminated string in `{}({})`", func, inner)),
+        },
+        _ => match inner.find(',') {
+            Some(i) => (inner[..i].trim(), &inner[i..]),
+            None => (inner, ""),
+        },
+    };
+    if action.is_empty() {
+        return Err(format!("`{}(...)` needs an action", func));
+    }
+
+    let rest = match rest {
+        "" => "",
+        _ => rest
+            .strip_prefix(',')
+            .ok_or_else(|| format!("expected `,` after the action in `{}({})`", func, inner))?,
+    };
+    let args = match rest.trim() {
+        "" => vec![],
+        rest => rest.split(',').map(str::trim).collect(),
+    };
+
+    Ok((action, args))
+}
+
+fn repeat_args(inner: &str) -> Result<Repeat<'_>, String> {
+    let (action, args) = split_action("repeat", inner)?;
+    let times = args
+        .into_iter()
+        .map(parse_duration)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (delay, interval, fastest) = match times[..] {
+        [] => (DEFAULT_DELAY, DEFAULT_INTERVAL, DEFAULT_INTERVAL),
+        [delay] => (delay, DEFAULT_INTERVAL, DEFAULT_INTERVAL),
+        [delay, interval] => (delay, interval, interval),
+        [delay, interval, fastest] => (delay, interval, fastest),
+        _ => {
+            return Err(format!(
+                "expected `repeat(<action>, <delay>, <rate>, <fastest rate>)`, found `repeat({})`",
+                inner
+            ));
+        }
+    };
+
+    if fastest.is_zero() {
+        return Err("a repeat rate has to be above 0ms".to_string());
+    }
+    if fastest > interval {
+        return Err(format!(
+            "the fastest rate `{:?}` is slower than the rate `{:?}`",
+            fastest, interval
+        ));
+    }
+
+    Ok(Repeat {
+        action,
+        delay,
+        interval,
+        fastest,
+    })
+}
+
+fn turbo_args(inner: &str) -> Result<Repeat<'_>, String> {
+    let (action, args) = split_action("turbo", inner)?;
+    let [rate] = args[..] else {
+        return Err(format!(
+            "expected `turbo(<action>, <rate>)` like `turbo(\"click\", 15hz)`, found `turbo({})`",
+            inner
+        ));
+    };
+
+    let number = rate
+        .strip_suffix("hz")
+        .or_else(|| rate.strip_suffix("Hz"))
+        .unwrap_or(rate);
+    let period = match number.trim().parse::<f64>() {
+        Ok(hz) if TURBO_RATES.contains(&hz) => Duration::from_secs_f64(1. / hz),
+        _ => {
+            return Err(format!(
+                "expected a rate from {}hz to {}hz like `15hz`, found `{}`",
+                TURBO_RATES.start(),
+                TURBO_RATES.end(),
+                rate
+            ));
+        }
+    };
+
+    Ok(Repeat {
+        action,
+        delay: period,
+        interval: period,
+        fastest: period,
+    })
+}
+
+struct Repeating {
+    /// the button that went down to start it
+    button: String,
+    turbo: bool,
+    action: String,
+    next: Instant,
+    interval: Duration,
+    fastest: Duration,
+}
+
+/// Sends actions again and again while they're held. A `repeat(...)` works like a keyboard's
+/// key repeat: only one repeats at a time, and pressing another button or releasing its own
+/// stops it. A `turbo(...)` keeps going until its own button is released.
+#[derive(Default)]
+pub struct Repeater {
+    repeating: Vec<Repeating>,
+}
+
+impl Repeater {
+    fn push(&mut self, button: &str, turbo: bool, repeat: &Repeat, now: Instant) {
+        send_action(repeat.action);
+        // a delay too long to count never runs out
+        let Some(next) = now.checked_add(repeat.delay) else {
+            return;
+        };
+        self.repeating.push(Repeating {
+            button: button.to_string(),
+            turbo,
+            action: repeat.action.to_string(),
+            next,
+            interval: repeat.interval,
+            fastest: repeat.fastest,
+        });
+    }
+
+    /// Sends a `repeat(...)` action now, and keeps sending it from `delay` on until `button`
+    /// is released or another one pressed.
+    pub fn start(&mut self, button: &str, repeat: &Repeat, now: Instant) {
+        self.stop();
+        self.push(button, false, repeat, now);
+    }
+
+    /// Fires a `turbo(...)` action now and at its rate until `button` is released.
+    pub fn turbo(&mut self, button: &str, repeat: &Repeat, now: Instant) {
+        debug!("turbo on: {} {:?}", button, repeat.action);
+        self.push(button, true, repeat, now);
+    }
+
+    /// Stops the repeat, turbos keep going.
+    pub fn stop(&mut self) {
+        self.repeating.retain(|r| r.turbo);
+    }
+
+    /// Stops whatever `button` started.
+    pub fn release(&mut self, button: &str) {
+        self.repeating.retain(|r| {
+            let held = r.button != button;
+            if !held && r.turbo {
+                debug!("turbo off: {} {:?}", button, r.action);
+            }
+            held
+        });
+    }
+
+    pub fn release_all(&mut self) {
+        self.repeating.clear();
+    }
+
+    /// Sends whatever is due. A late call sends each once rather than catching up.
+    pub fn poll(&mut self, now: Instant) {
+        self.repeating.retain_mut(|r| {
+            if now < r.next {
+                return true;
+            }
+
+            send_action(&r.action);
+            let Some(next) = r.next.checked_add(r.interval) else {
+                return false;
+            };
+            r.next = if next < now { now + r.interval } else { next };
+            r.interval = r.interval.mul_f64(ACCELERATION).max(r.fastest);
+            true
+        });
+    }
+
+    /// When `poll` next has something to do.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.repeating.iter().map(|r| r.next).min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{simulation::set_sink, sink::Recorder};
+
+    fn repeat(action: &str) -> Repeat<'_> {
+        parse_repeat(action).unwrap().unwrap()
+    }
+
+    #[test]
+    fn only_its_own_button_or_a_press_stops_a_repeat() {
+        set_sink(Box::new(Recorder::default()));
+        let now = Instant::now();
+        let mut repeater = Repeater::default();
+
+        repeater.start("a", &repeat(r#"repeat("down")"#), now);
+        repeater.release("b");
+        assert_eq!(repeater.next_deadline(), Some(now + DEFAULT_DELAY));
+        repeater.release("a");
+        assert_eq!(repeater.next_deadline(), None);
+
+        repeater.start("a", &repeat(r#"repeat("down")"#), now);
+        repeater.turbo(
+            "b",
+            &parse_turbo(r#"turbo("click", 10hz)"#).unwrap().unwrap(),
+            now,
+        );
+        repeater.stop();
+        assert_eq!(
+            repeater.next_deadline(),
+            Some(now + Duration::from_millis(100))
+        );
+        repeater.release("b");
+        assert_eq!(repeater.next_deadline(), None);
+    }
+
+    fn period(val: &str) -> Result<Duration, String> {
+        parse_turbo(val).unwrap().map(|t| t.interval)
+    }
+
+    #[test]
+    fn turbo_rates_stay_in_range() {
+        assert_eq!(
+            period(r#"turbo("x", 10hz)"#),
+            Ok(Duration::from_millis(100))
+        );
+        assert_eq!(period(r#"turbo("x", 4)"#), Ok(Duration::from_millis(250)));
+        for bad in [
+            "0hz", "-5hz", "1e-300hz", "0.01hz", "5000hz", "infhz", "fast",
+        ] {
+            let val = format!(r#"turbo("x", {})"#, bad);
+            assert!(period(&val).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn turbo_keeps_its_rate_until_its_button_is_released() {
+        set_sink(Box::new(Recorder::default()));
+        let now = Instant::now();
+        let period = Duration::from_millis(100);
+        let mut repeater = Repeater::default();
+
+        let turbo = parse_turbo(r#"turbo("click", 10hz)"#).unwrap().unwrap();
+        repeater.turbo("a", &turbo, now);
+        assert_eq!(repeater.next_deadline(), Some(now + period));
+        repeater.poll(now + period);
+        assert_eq!(repeater.next_deadline(), Some(now + period * 2));
+
+        // a late poll fires once and doesn't speed up to catch up
+        repeater.poll(now + period * 10);
+        assert_eq!(repeater.next_deadline(), Some(now + period * 11));
+
+        // other buttons, their repeats and their turbos leave it going
+        repeater.turbo("b", &turbo, now + period * 10);
+        repeater.start("c", &repeat(r#"repeat("down")"#), now + period * 10);
+        repeater.release("b");
+        repeater.release("c");
+        assert_eq!(repeater.next_deadline(), Some(now + period * 11));
+
+        repeater.release("a");
+        assert_eq!(repeater.next_deadline(), None);
+
+        repeater.turbo("a", &turbo, now);
+        repeater.turbo("b", &turbo, now);
+        repeater.release_all();
+        assert_eq!(repeater.next_deadline(), None);
+    }
+
+    #[test]
+    fn times_too_long_to_count_never_come() {
+        set_sink(Box::new(Recorder::default()));
+        let now = Instant::now();
+        let mut repeater = Repeater::default();
+
+        let mut forever = repeat(r#"repeat("down", 1s, 1s)"#);
+        forever.delay = Duration::MAX;
+        repeater.start("a", &forever, now);
+        assert_eq!(repeater.next_deadline(), None);
+
+        forever.delay = Duration::ZERO;
+        forever.interval = Duration::MAX;
+        repeater.start("a", &forever, now);
+        repeater.poll(now);
+        assert_eq!(repeater.next_deadline(), None);
+    }
+}
@@ -0,0 +1,361 @@
+use std::{collections::HashMap, time::Duration};
+
+use crate::layout::{LAYOUTS, Layout, find};
+
+/// How stick deflection past the deadzone maps to speed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Curve {
+    Linear,
+    /// `gamma(2.5)`, raises deflection to a power, higher is finer near the centre
+    Gamma(f64),
+}
+
+impl Curve {
+    pub fn apply(&self, v: f64) -> f64 {
+        match self {
+            Curve::Linear => v,
+            Curve::Gamma(g) => v.powf(*g),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeadzoneShape {
+    /// each axis is cut off on its own, good for sticking to straight lines
+    Axial,
+    /// the distance from the centre is cut off, good for smooth diagonals
+    Radial,
+}
+
+/// What a stick does while `mouse` is held.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StickRole {
+    Mouse,
+    /// the cursor at a quarter of `--mouse-speed`
+    MousePrecision,
+    Scroll,
+    ScrollHorizontalOnly,
+    /// holds the arrow keys it points at
+    Arrows,
+    /// holds W, A, S and D like arrows
+    Wasd,
+    None,
+}
+
+impl StickRole {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value.trim() {
+            "mouse" => Ok(StickRole::Mouse),
+            "mouse-precision" => Ok(StickRole::MousePrecision),
+            "scroll" => Ok(StickRole::Scroll),
+            "scroll-horizontal-only" => Ok(StickRole::ScrollHorizontalOnly),
+            "arrows" => Ok(StickRole::Arrows),
+            "wasd" => Ok(StickRole::Wasd),
+            "none" => Ok(StickRole::None),
+            v => Err(format!(
+                "expected `mouse`, `mouse-precision`, `scroll`, `scroll-horizontal-only`, \
+                 `arrows`, `wasd` or `none`, found `{}`",
+                v
+            )),
+        }
+    }
+}
+
+/// How many directions a stick has as buttons.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StickDirections {
+    /// `ls-up`, `ls-up-right`, ... with diagonals
+    Eight,
+    /// `ls-up`, `ls-right`, `ls-down` and `ls-left` only
+    Four,
+}
+
+/// Whether `rb-lt` needs RB pressed first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChordOrder {
+    /// only in the written order, `rb+lt` is the way to say any order
+    Pressed,
+    /// in any order, an exact ordered match still wins
+    Any,
+}
+
+/// Tuning read from `--` properties, which cascade through selectors like bindings do.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// `--mouse-speed`, pixels per second at full deflection
+    pub mouse_speed: f64,
+    /// `--scroll-speed`, wheel steps per second at full deflection
+    pub scroll_speed: f64,
+    /// `--mouse-curve`
+    pub curve: Curve,
+    /// `--deadzone`, fraction of the stick's travel that's ignored
+    pub deadzone: f64,
+    /// `--deadzone-shape`
+    pub deadzone_shape: DeadzoneShape,
+    /// `--hold-time`, how long a press lasts before it counts as `:hold`
+    pub hold_time: Duration,
+    /// `--double-tap-time`, how long after a tap a second one still makes it `:double`
+    pub double_tap_time: Duration,
+    /// `--chord-order`
+    pub chord_order: ChordOrder,
+    /// `--trigger-actuation`, how far an analog trigger goes down before it counts as pressed
+    pub trigger_actuation: f64,
+    /// `--trigger-full`, how far it goes down for `:full`
+    pub trigger_full: f64,
+    /// `--trigger-hysteresis`, how far back below a threshold it has to come to let go
+    pub trigger_hysteresis: f64,
+    /// `--stick-radius`, how far a stick goes out before a direction like `ls-up` is pressed
+    pub stick_radius: f64,
+    /// `--stick-directions`
+    pub stick_directions: StickDirections,
+    /// `--left-stick`
+    pub left_stick: StickRole,
+    /// `--right-stick`
+    pub right_stick: StickRole,
+    /// `--keyboard-layout`, `None` to use the system's
+    pub keyboard_layout: Option<&'static Layout>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            mouse_speed: 1200.,
+            scroll_speed: 15.,
+            curve: Curve::Gamma(3.),
+            deadzone: 0.,
+            deadzone_shape: DeadzoneShape::Axial,
+            hold_time: Duration::from_millis(200),
+            double_tap_time: Duration::from_millis(250),
+            chord_order: ChordOrder::Pressed,
+            trigger_actuation: 0.5,
+            trigger_full: 0.95,
+            trigger_hysteresis: 0.05,
+            stick_radius: 0.5,
+            stick_directions: StickDirections::Eight,
+            left_stick: StickRole::Mouse,
+            right_stick: StickRole::Scroll,
+            keyboard_layout: None,
+        }
+    }
+}
+
+fn parse_number(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(n) if n.is_finite() && n >= 0. => Ok(n),
+        _ => Err(format!("expected a non-negative number, found `{}`", value)),
+    }
+}
+
+/// `800ms`, `1.5s`, or a bare number of milliseconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+
+    let (number, scale) = if let Some(ms) = value.strip_suffix("ms") {
+        (ms, 1e-3)
+    } else if let Some(s) = value.strip_suffix('s') {
+        (s, 1.)
+    } else {
+        (value, 1e-3)
+    };
+
+    // too long for a `Duration` is as wrong as not a number
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| *n >= 0.)
+        .and_then(|n| Duration::try_from_secs_f64(n * scale).ok())
+        .ok_or_else(|| {
+            format!(
+                "expected a duration like `800ms` or `1.5s`, found `{}`",
+                value
+            )
+        })
+}
+
+/// A fraction of a trigger's or stick's travel, `0.5` is halfway.
+fn parse_fraction(value: &str) -> Result<f64, String> {
+    match parse_number(value)? {
+        n if n > 0. && n <= 1. => Ok(n),
+        _ => Err(format!(
+            "expected a number above 0 and up to 1, found `{}`",
+            value
+        )),
+    }
+}
+
+fn parse_curve(value: &str) -> Result<Curve, String> {
+    let value = value.trim();
+    if value == "linear" {
+        return Ok(Curve::Linear);
+    }
+
+    match value
+        .strip_prefix("gamma(")
+        .and_then(|v| v.strip_suffix(')'))
+    {
+        Some(g) => parse_number(g).map(Curve::Gamma),
+        None => Err(format!(
+            "expected `linear` or `gamma(<number>)`, found `{}`",
+            value
+        )),
+    }
+}
+
+impl Settings {
+    /// Applies one property, `Ok(false)` means it isn't a setting this knows about.
+    fn apply(&mut self, name: &str, value: &str) -> Result<bool, String> {
+        match name {
+            "--mouse-speed" => self.mouse_speed = parse_number(value)?,
+            "--scroll-speed" => self.scroll_speed = parse_number(value)?,
+            "--mouse-curve" => self.curve = parse_curve(value)?,
+            "--deadzone" => {
+                let dz = parse_number(value)?;
+                if dz >= 1. {
+                    return Err(format!("deadzone must be below 1, found `{}`", value));
+                }
+                self.deadzone = dz;
+            }
+            "--hold-time" => self.hold_time = parse_duration(value)?,
+            "--double-tap-time" => self.double_tap_time = parse_duration(value)?,
+            "--deadzone-shape" => {
+                self.deadzone_shape = match value.trim() {
+                    "axial" => DeadzoneShape::Axial,
+                    "radial" => DeadzoneShape::Radial,
+                    v => return Err(format!("expected `axial` or `radial`, found `{}`", v)),
+                }
+            }
+            "--trigger-actuation" => self.trigger_actuation = parse_fraction(value)?,
+            "--trigger-full" => self.trigger_full = parse_fraction(value)?,
+            "--trigger-hysteresis" => self.trigger_hysteresis = parse_number(value)?,
+            "--stick-radius" => self.stick_radius = parse_fraction(value)?,
+            "--stick-directions" => {
+                self.stick_directions = match value.trim() {
+                    "8" => StickDirections::Eight,
+                    "4" => StickDirections::Four,
+                    v => return Err(format!("expected `8` or `4`, found `{}`", v)),
+                }
+            }
+            "--left-stick" => self.left_stick = StickRole::parse(value)?,
+            "--right-stick" => self.right_stick = StickRole::parse(value)?,
+            "--keyboard-layout" => {
+                self.keyboard_layout = match value.trim() {
+                    "auto" => None,
+                    v => Some(find(v).ok_or_else(|| {
+                        let names: Vec<String> =
+                            LAYOUTS.iter().map(|l| format!("`{}`", l.name)).collect();
+                        format!(
+                            "expected `auto`, {} or {}, found `{}`",
+                            names[..names.len() - 1].join(", "),
+                            names[names.len() - 1],
+                            v
+                        )
+                    })?),
+                }
+            }
+            "--chord-order" => {
+                self.chord_order = match value.trim() {
+                    "pressed" => ChordOrder::Pressed,
+                    "any" => ChordOrder::Any,
+                    v => return Err(format!("expected `pressed` or `any`, found `{}`", v)),
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Reads the settings out of a query result, bad values are logged and left at their default.
+    pub fn from_query(query: &HashMap<String, String>) -> Self {
+        let mut settings = Self::default();
+
+        for (name, value) in query {
+            if !name.starts_with("--") {
+                continue;
+            }
+            if let Err(e) = settings.apply(name, value) {
+                warn!("ignoring {}: {}", name, e);
+            }
+        }
+
+        settings
+    }
+
+    /// The role of `stick`, which is `ls` or `rs`.
+    pub fn stick_role(&self, stick: &str) -> StickRole {
+        if stick == "ls" {
+            self.left_stick
+        } else {
+            self.right_stick
+        }
+    }
+
+    pub fn validate(name: &str, value: &str) -> Result<(), String> {
+        match Self::default().apply(name, value)? {
+            true => Ok(()),
+            false => Err(format!("unknown property `{}`", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("800ms"), Ok(Duration::from_millis(800)));
+        assert_eq!(parse_duration(" 1.5s "), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("250"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("0s"), Ok(Duration::ZERO));
+        for bad in ["", "ms", "-1s", "fast", "NaN", "infs", "1e300s", "1e300"] {
+            assert!(parse_duration(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn numbers_fractions_and_curves() {
+        assert_eq!(parse_number(" 1.5 "), Ok(1.5));
+        for bad in ["-1", "inf", "NaN", "fast"] {
+            assert!(parse_number(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(parse_fraction("1"), Ok(1.));
+        assert!(parse_fraction("0").is_err());
+        assert!(parse_fraction("1.01").is_err());
+        assert_eq!(parse_curve("linear"), Ok(Curve::Linear));
+        assert_eq!(parse_curve(" gamma(2.5) "), Ok(Curve::Gamma(2.5)));
+        for bad in ["gamma()", "gamma(-1)", "gamma(2", "cubic"] {
+            assert!(parse_curve(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn bad_settings_keep_their_default() {
+        let query: HashMap<String, String> = [
+            ("--hold-time", "300ms"),
+            ("--stick-directions", "4"),
+            ("--deadzone", "1"),
+            ("--trigger-full", "2"),
+            ("a", "b"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let settings = Settings::from_query(&query);
+        assert_eq!(settings.hold_time, Duration::from_millis(300));
+        assert_eq!(settings.stick_directions, StickDirections::Four);
+        assert_eq!(settings.deadzone, Settings::default().deadzone);
+        assert_eq!(settings.trigger_full, Settings::default().trigger_full);
+    }
+
+    #[test]
+    fn unknown_properties_are_errors() {
+        assert_eq!(Settings::validate("--left-stick", "wasd"), Ok(()));
+        assert!(Settings::validate("--left-stick", "wsad").is_err());
+        assert_eq!(
+            Settings::validate("--mouse-sped", "10"),
+            Err("unknown property `--mouse-sped`".to_string())
+        );
+    }
+}
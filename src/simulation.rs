@@ -1,48 +1,103 @@
-use mouse_position::mouse_position::Mouse;
-use rdev::{Button, EventType, Key, simulate};
-use std::{thread, time::Duration};
+use rdev::{Button, Key};
 
-pub fn scroll_mouse(dx: i64, dy: i64) {
-    simulate(&EventType::Wheel {
-        delta_x: dx,
-        delta_y: dy,
-    })
-    .unwrap();
+use std::sync::Mutex;
+
+use crate::{
+    keys::{modifier_key, named_key},
+    layout::{self, Layout},
+    sink::{InputSink, Rdev},
+};
+
+/// Where input goes, rdev until `set_sink` picks something else at startup.
+static SINK: Mutex<Option<Box<dyn InputSink>>> = Mutex::new(None);
+
+pub fn set_sink(sink: Box<dyn InputSink>) {
+    *SINK.lock().unwrap_or_else(|e| e.into_inner()) = Some(sink);
 }
 
-pub fn move_mouse(x: f64, y: f64) {
-    simulate(&EventType::MouseMove { x, y }).unwrap();
+fn with_sink<T>(f: impl FnOnce(&mut dyn InputSink) -> T) -> T {
+    let mut sink = SINK.lock().unwrap_or_else(|e| e.into_inner());
+    f(sink.get_or_insert_with(|| Box::new(Rdev)).as_mut())
 }
 
-pub fn move_mouse_delta(dx: f64, dy: f64) {
-    if let Mouse::Position { x, y } = Mouse::get_mouse_position() {
-        let new_x = x as f64 + dx;
-        let new_y = y as f64 + dy;
-        move_mouse(new_x, new_y);
+/// The sink from `set_sink`, locked for one event at a time so the pauses while text is typed
+/// don't hold up the pointer or input from other threads.
+struct Shared;
+
+impl InputSink for Shared {
+    fn key(&mut self, key: Key, down: bool) -> Result<(), String> {
+        with_sink(|sink| sink.key(key, down))
+    }
+
+    fn button(&mut self, button: Button, down: bool) -> Result<(), String> {
+        with_sink(|sink| sink.button(button, down))
+    }
+
+    fn move_by(&mut self, dx: f64, dy: f64) -> Result<(), String> {
+        with_sink(|sink| sink.move_by(dx, dy))
+    }
+
+    fn wheel(&mut self, dx: i64, dy: i64) -> Result<(), String> {
+        with_sink(|sink| sink.wheel(dx, dy))
+    }
+
+    fn copy(&mut self, text: &str) -> Result<(), String> {
+        with_sink(|sink| sink.copy(text))
     }
 }
 
-pub fn send_left_click(is_release: bool) {
-    if is_release {
-        simulate(&EventType::ButtonRelease(Button::Left)).unwrap();
-    } else {
-        simulate(&EventType::ButtonPress(Button::Left)).unwrap();
+fn report(result: Result<(), String>) {
+    if let Err(e) = result {
+        warn!("{}", e);
     }
 }
 
+fn send_key(key: Key, down: bool) {
+    report(Shared.key(key, down));
+}
+
+fn send_button(button: Button, is_release: bool) {
+    report(Shared.button(button, !is_release));
+}
+
+pub fn scroll_mouse(dx: i64, dy: i64) {
+    report(Shared.wheel(dx, dy));
+}
+
+pub fn move_mouse_delta(dx: f64, dy: f64) {
+    report(Shared.move_by(dx, dy));
+}
+
+pub fn send_left_click(is_release: bool) {
+    send_button(Button::Left, is_release);
+}
+
 pub fn send_right_click(is_release: bool) {
-    if is_release {
-        simulate(&EventType::ButtonRelease(Button::Right)).unwrap();
-    } else {
-        simulate(&EventType::ButtonPress(Button::Right)).unwrap();
-    }
+    send_button(Button::Right, is_release);
 }
 
 pub fn send_middle_click(is_release: bool) {
-    if is_release {
-        simulate(&EventType::ButtonRelease(Button::Middle)).unwrap();
-    } else {
-        simulate(&EventType::ButtonPress(Button::Middle)).unwrap();
+    send_button(Button::Middle, is_release);
+}
+
+pub const MOUSE_ACTIONS: [&str; 5] = ["mouse", "click", "leftclick", "rightclick", "middleclick"];
+
+/// Sends a whole click for a mouse button action, or the combo for anything else.
+pub fn send_action(action: &str) {
+    match action {
+        "click" | "leftclick" => {
+            send_left_click(false);
+            send_left_click(true);
+        }
+        "rightclick" => {
+            send_right_click(false);
+            send_right_click(true);
+        }
+        "middleclick" => {
+            send_middle_click(false);
+            send_middle_click(true);
+        }
+        _ => send_combo(action),
     }
 }
 
@@ -56,190 +111,167 @@ pub fn send_combo(combo: &str) {
     for p in parts {
         let token = p.to_lowercase();
 
-        match token.as_str() {
-            // modifiers
-            "ctrl" => modifiers.push(Key::ControlLeft),
-            "shift" => modifiers.push(Key::ShiftLeft),
-            "meta" => modifiers.push(Key::MetaLeft),
-            "alt" => modifiers.push(Key::Alt),
-
-            // arrows
-            "up" => specials.push(Key::UpArrow),
-            "down" => specials.push(Key::DownArrow),
-            "left" => specials.push(Key::LeftArrow),
-            "right" => specials.push(Key::RightArrow),
-
-            // other named keys
-            "tab" => specials.push(Key::Tab),
-            "enter" => specials.push(Key::Return),
-            "esc" => specials.push(Key::Escape),
-            "space" => send_char(' '),
-
-            // mouse
-            "mouse" | "click" | "leftclick" | "rightclick" | "middleclick" => {}
-
+        if let Some(key) = modifier_key(&token) {
+            modifiers.push(key);
+        } else if let Some(key) = named_key(&token) {
+            specials.push(key);
+        } else if !MOUSE_ACTIONS.contains(&token.as_str()) {
             // everything else = literal text
-            _ => text.push_str(p),
+            text.push_str(p);
         }
     }
 
     // hold modifiers
     for m in &modifiers {
-        simulate(&EventType::KeyPress(*m)).unwrap();
+        send_key(*m, true);
     }
 
     // press special keys
     for k in &specials {
-        simulate(&EventType::KeyPress(*k)).unwrap();
-        simulate(&EventType::KeyRelease(*k)).unwrap();
+        send_key(*k, true);
+        send_key(*k, false);
     }
 
     // type characters
-    for c in text.chars() {
-        send_char(c);
-    }
+    report(Shared.text(&text));
 
     // release modifiers
     for m in modifiers.iter().rev() {
-        simulate(&EventType::KeyRelease(*m)).unwrap();
-    }
-}
-
-fn send_char(c: char) {
-    let (key, needs_shift) = char_to_key(c);
-
-    if needs_shift {
-        simulate(&EventType::KeyPress(Key::ShiftLeft)).unwrap();
-    }
-
-    simulate(&EventType::KeyPress(key)).unwrap();
-    simulate(&EventType::KeyRelease(key)).unwrap();
-
-    if needs_shift {
-        simulate(&EventType::KeyRelease(Key::ShiftLeft)).unwrap();
-    }
-
-    thread::sleep(Duration::from_millis(5));
-}
-
-fn char_to_key(c: char) -> (Key, bool) {
-    use Key::*;
-
-    if c.is_ascii_uppercase() {
-        return (
-            match c.to_ascii_lowercase() {
-                'a' => KeyA,
-                'b' => KeyB,
-                'c' => KeyC,
-                'd' => KeyD,
-                'e' => KeyE,
-                'f' => KeyF,
-                'g' => KeyG,
-                'h' => KeyH,
-                'i' => KeyI,
-                'j' => KeyJ,
-                'k' => KeyK,
-                'l' => KeyL,
-                'm' => KeyM,
-                'n' => KeyN,
-                'o' => KeyO,
-                'p' => KeyP,
-                'q' => KeyQ,
-                'r' => KeyR,
-                's' => KeyS,
-                't' => KeyT,
-                'u' => KeyU,
-                'v' => KeyV,
-                'w' => KeyW,
-                'x' => KeyX,
-                'y' => KeyY,
-                'z' => KeyZ,
-                _ => unreachable!(),
-            },
-            true,
-        );
-    }
-
-    if c.is_ascii_lowercase() {
-        return (
-            match c {
-                'a' => KeyA,
-                'b' => KeyB,
-                'c' => KeyC,
-                'd' => KeyD,
-                'e' => KeyE,
-                'f' => KeyF,
-                'g' => KeyG,
-                'h' => KeyH,
-                'i' => KeyI,
-                'j' => KeyJ,
-                'k' => KeyK,
-                'l' => KeyL,
-                'm' => KeyM,
-                'n' => KeyN,
-                'o' => KeyO,
-                'p' => KeyP,
-                'q' => KeyQ,
-                'r' => KeyR,
-                's' => KeyS,
-                't' => KeyT,
-                'u' => KeyU,
-                'v' => KeyV,
-                'w' => KeyW,
-                'x' => KeyX,
-                'y' => KeyY,
-                'z' => KeyZ,
-                _ => unreachable!(),
+        send_key(*m, false);
+    }
+}
+
+/// The combo inside a `hold(...)` value, e.g. `shift` for `hold("shift")`.
+pub fn parse_hold(val: &str) -> Option<&str> {
+    let inner = val.strip_prefix("hold(")?.strip_suffix(')')?.trim();
+    Some(inner.trim_matches(|c| c == '"' || c == '\''))
+}
+
+/// Keys for a `hold(...)` combo. Only keys that exist on their own can be held, not text.
+pub fn hold_keys(combo: &str) -> Result<Vec<Key>, String> {
+    hold_keys_on(combo, layout::current())
+}
+
+/// `hold_keys` on `layout`. A letter or digit that needs shift or AltGr there, like `1` on
+/// AZERTY, holds those too.
+fn hold_keys_on(combo: &str, layout: &Layout) -> Result<Vec<Key>, String> {
+    let mut keys: Vec<Key> = vec![];
+    for token in combo.split('+') {
+        let name = token.to_lowercase();
+        if let Some(key) = modifier_key(&name).or_else(|| named_key(&name)) {
+            keys.push(key);
+            continue;
+        }
+
+        let stroke = match name.chars().collect::<Vec<_>>()[..] {
+            [c] if c.is_ascii_alphanumeric() => match layout.strokes(c).as_deref() {
+                Some([stroke]) => Some(*stroke),
+                _ => None,
             },
-            false,
-        );
-    }
-
-    match c {
-        '0' => (Num0, false),
-        '1' => (Num1, false),
-        '2' => (Num2, false),
-        '3' => (Num3, false),
-        '4' => (Num4, false),
-        '5' => (Num5, false),
-        '6' => (Num6, false),
-        '7' => (Num7, false),
-        '8' => (Num8, false),
-        '9' => (Num9, false),
-
-        '!' => (Num1, true),
-        '@' => (Num2, true),
-        '#' => (Num3, true),
-        '$' => (Num4, true),
-        '%' => (Num5, true),
-        '^' => (Num6, true),
-        '&' => (Num7, true),
-        '*' => (Num8, true),
-        '(' => (Num9, true),
-        ')' => (Num0, true),
-
-        ' ' => (Space, false),
-        '\n' => (Return, false),
-        '.' => (Dot, false),
-        ',' => (Comma, false),
-        '-' => (Minus, false),
-        '=' => (Equal, false),
-        '/' => (Slash, false),
-        ';' => (SemiColon, false),
-        '\'' => (Quote, false),
-
-        '>' => (Dot, true),
-        '<' => (Comma, true),
-        '_' => (Minus, true),
-        '+' => (Equal, true),
-        '?' => (Slash, true),
-        ':' => (SemiColon, true),
-        '"' => (Quote, true),
-        '{' => (LeftBracket, true),
-        '}' => (RightBracket, true),
-        '|' => (BackSlash, true),
-        '~' => (BackQuote, true),
-
-        _ => panic!("Unsupported character: {c:?}"),
+            _ => None,
+        };
+        let Some(stroke) = stroke else {
+            return Err(format!(
+                "`{}` can't be held, only modifiers, named keys, letters and digits can",
+                token
+            ));
+        };
+        if stroke.altgr {
+            keys.push(Key::AltGr);
+        }
+        if stroke.shift {
+            keys.push(Key::ShiftLeft);
+        }
+        keys.push(stroke.key);
+    }
+
+    // `shift+1` on AZERTY would hold shift twice
+    let mut unique = vec![];
+    for key in keys {
+        if !unique.contains(&key) {
+            unique.push(key);
+        }
+    }
+    Ok(unique)
+}
+
+/// Keys pressed by `hold(...)` bindings, remembered per gamepad button so they go up with it.
+#[derive(Default)]
+pub struct HeldKeys {
+    held: Vec<(String, Vec<Key>)>,
+}
+
+impl HeldKeys {
+    fn is_down(&self, key: Key) -> bool {
+        self.held.iter().any(|(_, keys)| keys.contains(&key))
+    }
+
+    /// Presses the keys of `combo` until `button` is released.
+    pub fn press(&mut self, button: &str, combo: &str) {
+        let keys = match hold_keys(combo) {
+            Ok(keys) => keys,
+            Err(e) => {
+                warn!("{}", e);
+                return;
+            }
+        };
+
+        // another button may already hold the same key
+        for key in &keys {
+            if !self.is_down(*key) {
+                send_key(*key, true);
+            }
+        }
+        self.held.push((button.to_string(), keys));
+    }
+
+    /// Releases whatever `button` holds, except keys another held button also holds.
+    pub fn release(&mut self, button: &str) {
+        let (released, kept): (Vec<_>, Vec<_>) =
+            self.held.drain(..).partition(|(b, _)| b == button);
+        self.held = kept;
+
+        let mut sent: Vec<Key> = vec![];
+        for key in released
+            .iter()
+            .rev()
+            .flat_map(|(_, keys)| keys.iter().rev())
+        {
+            if !self.is_down(*key) && !sent.contains(key) {
+                send_key(*key, false);
+                sent.push(*key);
+            }
+        }
+    }
+
+    pub fn release_all(&mut self) {
+        let buttons: Vec<String> = self.held.iter().map(|(b, _)| b.clone()).collect();
+        for button in buttons {
+            self.release(&button);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::find;
+
+    fn held(combo: &str, layout: &str) -> Result<Vec<Key>, String> {
+        hold_keys_on(combo, find(layout).unwrap())
+    }
+
+    #[test]
+    fn held_characters_bring_their_modifiers() {
+        assert_eq!(held("1", "us"), Ok(vec![Key::Num1]));
+        assert_eq!(held("1", "fr"), Ok(vec![Key::ShiftLeft, Key::Num1]));
+        assert_eq!(held("shift+1", "fr"), Ok(vec![Key::ShiftLeft, Key::Num1]));
+        assert_eq!(held("ctrl+y", "de"), Ok(vec![Key::ControlLeft, Key::KeyZ]));
+    }
+
+    #[test]
+    fn only_keys_can_be_held() {
+        assert!(held("hello", "us").is_err());
+        assert!(held("ctrl+é", "us").is_err());
     }
 }
@@ -0,0 +1,406 @@
+use mouse_position::mouse_position::Mouse;
+use rdev::{Button, EventType, Key, simulate};
+
+use std::{collections::VecDeque, thread, time::Duration};
+
+use crate::{
+    clipboard,
+    layout::{self, Stroke},
+};
+
+/// Where simulated input goes. Text is typed through the keys and the clipboard by default,
+/// so a backend only has to know how to press things.
+pub trait InputSink: Send {
+    fn key(&mut self, key: Key, down: bool) -> Result<(), String>;
+
+    fn button(&mut self, button: Button, down: bool) -> Result<(), String>;
+
+    /// Moves the pointer by `dx`, `dy` pixels.
+    fn move_by(&mut self, dx: f64, dy: f64) -> Result<(), String>;
+
+    /// Scrolls by wheel steps, positive is right and up.
+    fn wheel(&mut self, dx: i64, dy: i64) -> Result<(), String>;
+
+    /// Puts `text` on the clipboard.
+    fn copy(&mut self, text: &str) -> Result<(), String> {
+        clipboard::copy(text)
+    }
+
+    /// Puts `text` in through the clipboard and ctrl+v, which replaces what was on it.
+    fn paste(&mut self, text: &str) -> Result<(), String> {
+        self.copy(text)?;
+
+        #[cfg(target_os = "macos")]
+        let modifier = Key::MetaLeft;
+        #[cfg(not(target_os = "macos"))]
+        let modifier = Key::ControlLeft;
+
+        let result = self
+            .key(modifier, true)
+            .and(self.key(Key::KeyV, true))
+            .and(self.key(Key::KeyV, false))
+            .and(self.key(modifier, false));
+
+        // give the app time to read the clipboard before anything else is typed
+        thread::sleep(Duration::from_millis(50));
+        result
+    }
+
+    /// Types `text` key by key. Characters without a key of their own are pasted instead.
+    fn text(&mut self, text: &str) -> Result<(), String> {
+        let mut unkeyed = String::new();
+        let layout = layout::current();
+        for c in text.chars() {
+            match layout.strokes(c) {
+                Some(strokes) => {
+                    paste_unkeyed(self, &mut unkeyed);
+                    for stroke in strokes {
+                        self.stroke(stroke)?;
+                    }
+                }
+                None => unkeyed.push(c),
+            }
+        }
+        paste_unkeyed(self, &mut unkeyed);
+        Ok(())
+    }
+
+    /// Presses one key with the modifiers that pick its level.
+    fn stroke(&mut self, stroke: Stroke) -> Result<(), String> {
+        let mut modifiers = vec![];
+        if stroke.altgr {
+            modifiers.push(Key::AltGr);
+        }
+        if stroke.shift {
+            modifiers.push(Key::ShiftLeft);
+        }
+
+        // every event goes out even after one fails, so nothing is left held
+        let mut result = Ok(());
+        for m in &modifiers {
+            result = result.and(self.key(*m, true));
+        }
+        result = result
+            .and(self.key(stroke.key, true))
+            .and(self.key(stroke.key, false));
+        for m in modifiers.iter().rev() {
+            result = result.and(self.key(*m, false));
+        }
+
+        thread::sleep(Duration::from_millis(5));
+        result
+    }
+}
+
+/// Pastes `text` and empties it, so runs of characters are pasted in one go. What can't be
+/// pasted is skipped with a warning.
+fn paste_unkeyed<S: InputSink + ?Sized>(sink: &mut S, text: &mut String) {
+    if text.is_empty() {
+        return;
+    }
+    let text = std::mem::take(text);
+
+    if let Err(e) = sink.paste(&text) {
+        for c in text.chars() {
+            warn!("can't type {:?}: {}", c, e);
+        }
+    }
+}
+
+/// Which `InputSink` sends input, picked with `--backend`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// uinput under Wayland, where X11 can't reach other windows, rdev elsewhere
+    Auto,
+    Rdev,
+    Uinput,
+}
+
+impl Backend {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "auto" => Some(Backend::Auto),
+            "rdev" => Some(Backend::Rdev),
+            "uinput" => Some(Backend::Uinput),
+            _ => None,
+        }
+    }
+}
+
+/// Sends input with rdev, which is XTest on X11 and the native APIs on macOS and Windows.
+pub struct Rdev;
+
+impl Rdev {
+    fn send(&self, event: EventType) -> Result<(), String> {
+        simulate(&event).map_err(|e| format!("couldn't send {:?}: {:?}", event, e))
+    }
+}
+
+impl InputSink for Rdev {
+    fn key(&mut self, key: Key, down: bool) -> Result<(), String> {
+        self.send(match down {
+            true => EventType::KeyPress(key),
+            false => EventType::KeyRelease(key),
+        })
+    }
+
+    fn button(&mut self, button: Button, down: bool) -> Result<(), String> {
+        self.send(match down {
+            true => EventType::ButtonPress(button),
+            false => EventType::ButtonRelease(button),
+        })
+    }
+
+    fn move_by(&mut self, dx: f64, dy: f64) -> Result<(), String> {
+        match Mouse::get_mouse_position() {
+            Mouse::Position { x, y } => self.send(EventType::MouseMove {
+                x: x as f64 + dx,
+                y: y as f64 + dy,
+            }),
+            // nothing to move from, the next frame tries again
+            Mouse::Error => Ok(()),
+        }
+    }
+
+    fn wheel(&mut self, dx: i64, dy: i64) -> Result<(), String> {
+        self.send(EventType::Wheel {
+            delta_x: dx,
+            delta_y: dy,
+        })
+    }
+}
+
+/// What a `Recorder` was asked to send.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recorded {
+    KeyDown(Key),
+    KeyUp(Key),
+    ButtonDown(Button),
+    ButtonUp(Button),
+    MoveBy { dx: f64, dy: f64 },
+    Wheel { dx: i64, dy: i64 },
+    Clipboard(String),
+}
+
+/// Keeps input instead of sending it, and logs it, for `--dry-run` and tests.
+#[derive(Default)]
+pub struct Recorder {
+    events: VecDeque<Recorded>,
+}
+
+/// How many events a `Recorder` keeps, a dry run can go on for hours.
+const RECORDER_LIMIT: usize = 1000;
+
+impl Recorder {
+    fn record(&mut self, event: Recorded) -> Result<(), String> {
+        // pointer motion arrives every frame, keep it out of the default log
+        match event {
+            Recorded::MoveBy { .. } | Recorded::Wheel { .. } => debug!("dry run: {:?}", event),
+            _ => info!("dry run: {:?}", event),
+        }
+
+        if self.events.len() == RECORDER_LIMIT {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+        Ok(())
+    }
+
+    /// Hands over what was recorded so far, oldest first, and starts over.
+    #[cfg(test)]
+    pub fn take(&mut self) -> Vec<Recorded> {
+        self.events.drain(..).collect()
+    }
+}
+
+impl InputSink for Recorder {
+    fn key(&mut self, key: Key, down: bool) -> Result<(), String> {
+        self.record(match down {
+            true => Recorded::KeyDown(key),
+            false => Recorded::KeyUp(key),
+        })
+    }
+
+    fn button(&mut self, button: Button, down: bool) -> Result<(), String> {
+        self.record(match down {
+            true => Recorded::ButtonDown(button),
+            false => Recorded::ButtonUp(button),
+        })
+    }
+
+    fn move_by(&mut self, dx: f64, dy: f64) -> Result<(), String> {
+        self.record(Recorded::MoveBy { dx, dy })
+    }
+
+    fn wheel(&mut self, dx: i64, dy: i64) -> Result<(), String> {
+        self.record(Recorded::Wheel { dx, dy })
+    }
+
+    fn copy(&mut self, text: &str) -> Result<(), String> {
+        self.record(Recorded::Clipboard(text.to_string()))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open_uinput() -> Result<Box<dyn InputSink>, String> {
+    crate::uinput::Uinput::create().map(|u| Box::new(u) as Box<dyn InputSink>)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_uinput() -> Result<Box<dyn InputSink>, String> {
+    Err("uinput is only on Linux".to_string())
+}
+
+/// The sink `backend` stands for, falling back to rdev when uinput can't be opened.
+pub fn open(backend: Backend, dry_run: bool) -> Box<dyn InputSink> {
+    if dry_run {
+        return Box::new(Recorder::default());
+    }
+
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some_and(|d| !d.is_empty());
+    let uinput = match backend {
+        Backend::Rdev => false,
+        Backend::Uinput => true,
+        Backend::Auto => cfg!(target_os = "linux") && wayland,
+    };
+    if !uinput {
+        return Box::new(Rdev);
+    }
+
+    match open_uinput() {
+        Ok(sink) => {
+            info!("sending input through uinput");
+            sink
+        }
+        Err(e) => {
+            warn!("{}, sending input through rdev instead", e);
+            Box::new(Rdev)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{Layout, find, set_current};
+    use Recorded::*;
+    use std::sync::Mutex;
+
+    /// The layout is global, tests that type have to take turns.
+    static LAYOUT: Mutex<()> = Mutex::new(());
+
+    fn typed(layout: &str, text: &str) -> Vec<Recorded> {
+        let _guard = LAYOUT.lock().unwrap_or_else(|e| e.into_inner());
+        let layout: &'static Layout = find(layout).unwrap();
+        set_current(Some(layout));
+
+        let mut recorder = Recorder::default();
+        recorder.text(text).unwrap();
+        recorder.take()
+    }
+
+    fn tap(key: Key) -> [Recorded; 2] {
+        [KeyDown(key), KeyUp(key)]
+    }
+
+    #[test]
+    fn stroke_wraps_the_key_in_altgr_then_shift() {
+        let mut recorder = Recorder::default();
+        recorder
+            .stroke(Stroke {
+                key: Key::KeyQ,
+                shift: true,
+                altgr: true,
+            })
+            .unwrap();
+
+        assert_eq!(
+            recorder.take(),
+            [
+                KeyDown(Key::AltGr),
+                KeyDown(Key::ShiftLeft),
+                KeyDown(Key::KeyQ),
+                KeyUp(Key::KeyQ),
+                KeyUp(Key::ShiftLeft),
+                KeyUp(Key::AltGr),
+            ]
+        );
+    }
+
+    #[test]
+    fn text_follows_the_layout() {
+        assert_eq!(typed("us", "y"), tap(Key::KeyY));
+        assert_eq!(typed("de", "y"), tap(Key::KeyZ));
+        assert_eq!(
+            typed("de", "Z"),
+            [
+                KeyDown(Key::ShiftLeft),
+                KeyDown(Key::KeyY),
+                KeyUp(Key::KeyY),
+                KeyUp(Key::ShiftLeft),
+            ]
+        );
+        assert_eq!(
+            typed("de", "@"),
+            [
+                KeyDown(Key::AltGr),
+                KeyDown(Key::KeyQ),
+                KeyUp(Key::KeyQ),
+                KeyUp(Key::AltGr),
+            ]
+        );
+        assert_eq!(typed("fr", "é"), tap(Key::Num2));
+    }
+
+    #[test]
+    fn accents_go_through_dead_keys() {
+        assert_eq!(typed("de", "é"), [tap(Key::Equal), tap(Key::KeyE)].concat());
+        assert_eq!(
+            typed("fr", "ê"),
+            [tap(Key::LeftBracket), tap(Key::KeyE)].concat()
+        );
+        // a dead key on its own is the dead key and space
+        assert_eq!(
+            typed("de", "^"),
+            [tap(Key::BackQuote), tap(Key::Space)].concat()
+        );
+    }
+
+    fn pasted(text: &str) -> Vec<Recorded> {
+        vec![
+            Clipboard(text.to_string()),
+            KeyDown(Key::ControlLeft),
+            KeyDown(Key::KeyV),
+            KeyUp(Key::KeyV),
+            KeyUp(Key::ControlLeft),
+        ]
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn characters_without_keys_are_pasted_in_runs() {
+        assert_eq!(
+            typed("us", "a😀“b"),
+            [
+                tap(Key::KeyA).to_vec(),
+                pasted("😀“"),
+                tap(Key::KeyB).to_vec()
+            ]
+            .concat()
+        );
+        assert_eq!(typed("us", "ñ"), pasted("ñ"));
+    }
+
+    #[test]
+    fn recorder_keeps_the_latest_events() {
+        let mut recorder = Recorder::default();
+        for dx in 0..RECORDER_LIMIT as i64 + 5 {
+            recorder.wheel(dx, 0).unwrap();
+        }
+
+        let events = recorder.take();
+        assert_eq!(events.len(), RECORDER_LIMIT);
+        assert_eq!(events[0], Wheel { dx: 5, dy: 0 });
+        assert!(recorder.take().is_empty());
+    }
+}
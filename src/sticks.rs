@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use crate::{
+    chord,
+    gestures::split_gesture,
+    settings::{Settings, StickDirections, StickRole},
+    triggers::Edge,
+};
+
+/// Directions in counter-clockwise order from right, one sector each.
+const LS: [&str; 8] = [
+    "ls-right",
+    "ls-up-right",
+    "ls-up",
+    "ls-up-left",
+    "ls-left",
+    "ls-down-left",
+    "ls-down",
+    "ls-down-right",
+];
+const RS: [&str; 8] = [
+    "rs-right",
+    "rs-up-right",
+    "rs-up",
+    "rs-up-left",
+    "rs-left",
+    "rs-down-left",
+    "rs-down",
+    "rs-down-right",
+];
+
+/// Every stick direction that can be bound like a button.
+pub const STICK_BUTTONS: [&str; 16] = [
+    LS[0], LS[1], LS[2], LS[3], LS[4], LS[5], LS[6], LS[7], RS[0], RS[1], RS[2], RS[3], RS[4],
+    RS[5], RS[6], RS[7],
+];
+
+/// How far past the radius or a sector's edge the stick has to come back before it lets go.
+const RADIUS_HYSTERESIS: f64 = 0.05;
+const ANGLE_HYSTERESIS: f64 = 5.;
+
+/// Whether anything in `bindings` uses a direction of `stick`, which is `ls` or `rs`.
+pub fn uses_stick(stick: &str, bindings: &HashMap<String, String>) -> bool {
+    bindings.keys().any(|name| {
+        chord::buttons(split_gesture(name).0)
+            .iter()
+            .any(|b| STICK_BUTTONS.contains(b) && b.starts_with(stick))
+    })
+}
+
+/// What an `arrows` or `wasd` stick holds while it points in `direction`, e.g. `up+left` for
+/// `ls-up-left`.
+pub fn role_keys(role: StickRole, direction: &str) -> Option<String> {
+    let keys: Vec<&str> = direction
+        .split('-')
+        .skip(1)
+        .map(|d| match (role, d) {
+            (StickRole::Wasd, "up") => "w",
+            (StickRole::Wasd, "left") => "a",
+            (StickRole::Wasd, "down") => "s",
+            (StickRole::Wasd, "right") => "d",
+            _ => d,
+        })
+        .collect();
+
+    matches!(role, StickRole::Arrows | StickRole::Wasd).then(|| keys.join("+"))
+}
+
+/// Who holds the keys of an `arrows` or `wasd` direction, named so it can't clash with the
+/// `ls-up` button.
+pub fn role_owner(direction: &str) -> String {
+    format!("--{}", direction)
+}
+
+/// The direction `(x, y)` points in, keeping `current` while it's only just past its sector.
+fn direction(
+    stick: &str,
+    (x, y): (f64, f64),
+    current: Option<&'static str>,
+    settings: &Settings,
+) -> Option<&'static str> {
+    let names = if stick == "ls" { &LS } else { &RS };
+    let radius = settings.stick_radius;
+
+    let r = x.hypot(y);
+    if r < radius - RADIUS_HYSTERESIS || (current.is_none() && r < radius) {
+        return None;
+    }
+
+    // y points up, so this is counter-clockwise from right like `names`
+    let angle = y.atan2(x).to_degrees().rem_euclid(360.);
+    let step = match settings.stick_directions {
+        StickDirections::Eight => 1,
+        StickDirections::Four => 2,
+    };
+    let width = 45. * step as f64;
+
+    if let Some(current) = current {
+        let i = names.iter().position(|n| *n == current).unwrap_or(0);
+        let off = (angle - i as f64 * 45. + 180.).rem_euclid(360.) - 180.;
+        if i % step == 0 && off.abs() <= width / 2. + ANGLE_HYSTERESIS {
+            return Some(current);
+        }
+    }
+
+    let sector = (angle / width).round() as usize % (8 / step);
+    Some(names[sector * step])
+}
+
+/// Turns stick positions into presses and releases of direction buttons like `ls-up`.
+#[derive(Default)]
+pub struct Sticks {
+    /// the direction each stick is pressed in
+    held: HashMap<&'static str, &'static str>,
+}
+
+impl Sticks {
+    /// A new position for `stick`. While it's not `enabled` it only lets go of what it held.
+    pub fn update(
+        &mut self,
+        stick: &'static str,
+        (x, y): (f32, f32),
+        enabled: bool,
+        settings: &Settings,
+    ) -> Vec<(&'static str, Edge)> {
+        let current = self.held.get(stick).copied();
+        let next = if enabled {
+            direction(stick, (x as f64, y as f64), current, settings)
+        } else {
+            None
+        };
+        if next == current {
+            return vec![];
+        }
+
+        let mut edges = vec![];
+        if let Some(old) = current {
+            edges.push((old, Edge::Release));
+            self.held.remove(stick);
+        }
+        if let Some(new) = next {
+            edges.push((new, Edge::Press));
+            self.held.insert(stick, new);
+        }
+        edges
+    }
+
+    /// Lets go of every direction.
+    pub fn release_all(&mut self) -> Vec<(&'static str, Edge)> {
+        self.held.drain().map(|(_, d)| (d, Edge::Release)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A point `r` out from the centre at `degrees` counter-clockwise from right.
+    fn at(r: f64, degrees: f64) -> (f64, f64) {
+        let a = degrees.to_radians();
+        (r * a.cos(), r * a.sin())
+    }
+
+    #[test]
+    fn eight_way_sectors() {
+        let settings = Settings::default();
+        let cases = [
+            (0., "ls-right"),
+            (22., "ls-right"),
+            (23., "ls-up-right"),
+            (90., "ls-up"),
+            (135., "ls-up-left"),
+            (180., "ls-left"),
+            (225., "ls-down-left"),
+            (270., "ls-down"),
+            (315., "ls-down-right"),
+            (338., "ls-right"),
+            (-10., "ls-right"),
+        ];
+        for (degrees, name) in cases {
+            assert_eq!(
+                direction("ls", at(1., degrees), None, &settings),
+                Some(name),
+                "{}",
+                degrees
+            );
+        }
+        assert_eq!(direction("rs", at(1., 90.), None, &settings), Some("rs-up"));
+    }
+
+    #[test]
+    fn four_way_sectors() {
+        let settings = Settings {
+            stick_directions: StickDirections::Four,
+            ..Settings::default()
+        };
+        let cases = [
+            (0., "ls-right"),
+            (44., "ls-right"),
+            (46., "ls-up"),
+            (134., "ls-up"),
+            (136., "ls-left"),
+            (225.5, "ls-down"),
+            (316., "ls-right"),
+        ];
+        for (degrees, name) in cases {
+            assert_eq!(
+                direction("ls", at(1., degrees), None, &settings),
+                Some(name),
+                "{}",
+                degrees
+            );
+        }
+    }
+
+    #[test]
+    fn radius_cut_off() {
+        let settings = Settings::default();
+        assert_eq!(direction("ls", at(0.49, 90.), None, &settings), None);
+        assert_eq!(
+            direction("ls", at(0.5, 90.), None, &settings),
+            Some("ls-up")
+        );
+
+        // once pressed it holds until it comes back past the hysteresis
+        let up = Some("ls-up");
+        assert_eq!(direction("ls", at(0.46, 90.), up, &settings), up);
+        assert_eq!(direction("ls", at(0.44, 90.), up, &settings), None);
+    }
+
+    #[test]
+    fn sectors_hold_a_little_past_their_edge() {
+        let settings = Settings::default();
+        let up = Some("ls-up");
+        assert_eq!(direction("ls", at(1., 64.), up, &settings), up);
+        assert_eq!(
+            direction("ls", at(1., 62.), up, &settings),
+            Some("ls-up-right")
+        );
+
+        // a diagonal held from 8-way isn't a 4-way direction, so it moves on right away
+        let four = Settings {
+            stick_directions: StickDirections::Four,
+            ..Settings::default()
+        };
+        let diagonal = Some("ls-up-right");
+        assert_eq!(direction("ls", at(1., 46.), diagonal, &four), Some("ls-up"));
+    }
+
+    #[test]
+    fn a_new_direction_releases_the_old_one_first() {
+        let settings = Settings::default();
+        let mut sticks = Sticks::default();
+        let point = |degrees: f64| {
+            let (x, y) = at(1., degrees);
+            (x as f32, y as f32)
+        };
+
+        assert_eq!(
+            sticks.update("ls", point(90.), true, &settings),
+            [("ls-up", Edge::Press)]
+        );
+        assert_eq!(sticks.update("ls", point(80.), true, &settings), []);
+        assert_eq!(
+            sticks.update("ls", point(180.), true, &settings),
+            [("ls-up", Edge::Release), ("ls-left", Edge::Press)]
+        );
+        assert_eq!(
+            sticks.update("ls", point(180.), false, &settings),
+            [("ls-left", Edge::Release)]
+        );
+        assert_eq!(sticks.update("ls", (0., 0.), true, &settings), []);
+
+        sticks.update("rs", point(270.), true, &settings);
+        assert_eq!(sticks.release_all(), [("rs-down", Edge::Release)]);
+        assert_eq!(sticks.release_all(), []);
+    }
+
+    #[test]
+    fn roles_hold_keys_for_directions() {
+        assert_eq!(
+            role_keys(StickRole::Arrows, "ls-up-left").as_deref(),
+            Some("up+left")
+        );
+        assert_eq!(
+            role_keys(StickRole::Wasd, "rs-down-right").as_deref(),
+            Some("s+d")
+        );
+        assert_eq!(role_keys(StickRole::Mouse, "ls-up"), None);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::simulation::{
+    HeldKeys, hold_keys, send_left_click, send_middle_click, send_right_click,
+};
+
+/// The action inside a `toggle(...)` value, e.g. `shift` for `toggle("shift")`.
+pub fn parse_toggle(val: &str) -> Option<&str> {
+    let inner = val.strip_prefix("toggle(")?.strip_suffix(')')?.trim();
+    Some(inner.trim_matches(|c| c == '"' || c == '\''))
+}
+
+/// Presses or releases the mouse button a `click` action clicks, `None` if it isn't one.
+fn mouse_button(action: &str) -> Option<fn(bool)> {
+    match action {
+        "click" | "leftclick" => Some(send_left_click),
+        "rightclick" => Some(send_right_click),
+        "middleclick" => Some(send_middle_click),
+        _ => None,
+    }
+}
+
+/// Whether `action` can be latched: a mouse button, or keys `hold(...)` could hold.
+pub fn check_toggle(action: &str) -> Result<(), String> {
+    match mouse_button(action) {
+        Some(_) => Ok(()),
+        None => hold_keys(action).map(|_| ()),
+    }
+}
+
+/// Named so it can't clash with a gamepad button holding keys of its own.
+fn owner(action: &str) -> String {
+    format!("toggle({})", action)
+}
+
+fn let_go(action: &str, held_keys: &mut HeldKeys) {
+    match mouse_button(action) {
+        Some(send) => send(true),
+        None => held_keys.release(&owner(action)),
+    }
+    debug!("toggle off: {:?}", action);
+}
+
+/// Keys and mouse buttons latched down by `toggle(...)` bindings, each until the same action
+/// is toggled again.
+#[derive(Default)]
+pub struct Toggles {
+    on: Vec<String>,
+}
+
+impl Toggles {
+    /// Latches `action` down, or lets go of it if it already is.
+    pub fn toggle(&mut self, action: &str, held_keys: &mut HeldKeys) {
+        if let Some(pos) = self.on.iter().position(|a| a == action) {
+            self.on.remove(pos);
+            let_go(action, held_keys);
+            return;
+        }
+
+        match mouse_button(action) {
+            Some(send) => send(false),
+            None => held_keys.press(&owner(action), action),
+        }
+        self.on.push(action.to_string());
+        debug!("toggle on: {:?}", action);
+    }
+
+    /// Lets go of everything that's latched.
+    pub fn release_all(&mut self, held_keys: &mut HeldKeys) {
+        for action in self.on.drain(..) {
+            let_go(&action, held_keys);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{simulation::set_sink, sink::Recorder};
+
+    #[test]
+    fn toggles_are_parsed_and_checked() {
+        assert_eq!(parse_toggle(r#"toggle("shift")"#), Some("shift"));
+        assert_eq!(parse_toggle("toggle( 'ctrl+alt' )"), Some("ctrl+alt"));
+        assert_eq!(parse_toggle("hold(shift)"), None);
+
+        assert_eq!(check_toggle("click"), Ok(()));
+        assert_eq!(check_toggle("shift"), Ok(()));
+        assert!(check_toggle("hello").is_err());
+    }
+
+    #[test]
+    fn a_second_toggle_lets_go() {
+        set_sink(Box::new(Recorder::default()));
+        let mut held_keys = HeldKeys::default();
+        let mut toggles = Toggles::default();
+
+        toggles.toggle("shift", &mut held_keys);
+        toggles.toggle("click", &mut held_keys);
+        assert_eq!(toggles.on, ["shift", "click"]);
+
+        toggles.toggle("shift", &mut held_keys);
+        assert_eq!(toggles.on, ["click"]);
+        toggles.toggle("shift", &mut held_keys);
+        assert_eq!(toggles.on, ["click", "shift"]);
+
+        toggles.release_all(&mut held_keys);
+        assert!(toggles.on.is_empty());
+        toggles.toggle("click", &mut held_keys);
+        assert_eq!(toggles.on, ["click"]);
+    }
+}
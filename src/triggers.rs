@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use crate::settings::Settings;
+
+/// A change in what a button counts as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    Press,
+    /// pressed all the way down, for `:full`
+    Full,
+    Release,
+}
+
+#[derive(Default)]
+struct Trigger {
+    /// reported a value between 0 and 1, so its presses come from `--trigger-actuation`
+    analog: bool,
+    pressed: bool,
+    full: bool,
+}
+
+/// Turns analog button values into presses and releases, with hysteresis so a trigger resting
+/// on its threshold doesn't chatter. Buttons are only treated as analog once they report a
+/// value between 0 and 1, until then gilrs' own presses and releases are used.
+#[derive(Default)]
+pub struct Triggers {
+    buttons: HashMap<&'static str, Trigger>,
+}
+
+impl Triggers {
+    /// A press or release as gilrs reports it, dropped for analog buttons.
+    pub fn digital(&mut self, button: &'static str, pressed: bool) -> Vec<(&'static str, Edge)> {
+        let t = self.buttons.entry(button).or_default();
+        if t.analog {
+            return vec![];
+        }
+
+        t.pressed = pressed;
+        let edge = if pressed { Edge::Press } else { Edge::Release };
+        vec![(button, edge)]
+    }
+
+    /// A new value for `button`, 0 released and 1 all the way down.
+    pub fn changed(
+        &mut self,
+        button: &'static str,
+        value: f32,
+        settings: &Settings,
+    ) -> Vec<(&'static str, Edge)> {
+        let t = self.buttons.entry(button).or_default();
+        if !t.analog {
+            if value <= 0. || value >= 1. {
+                return vec![];
+            }
+            t.analog = true;
+        }
+
+        let value = value as f64;
+        let actuation = settings.trigger_actuation;
+        let full = settings.trigger_full.max(actuation);
+        let hysteresis = settings.trigger_hysteresis;
+
+        let mut edges = vec![];
+        if !t.pressed && value >= actuation {
+            t.pressed = true;
+            edges.push((button, Edge::Press));
+        }
+        if t.pressed && !t.full && value >= full {
+            t.full = true;
+            edges.push((button, Edge::Full));
+        }
+        if t.full && value < full - hysteresis {
+            t.full = false;
+        }
+        // a fully released trigger always lets go, however large the hysteresis
+        if t.pressed && (value < actuation - hysteresis || value <= 0.) {
+            t.pressed = false;
+            t.full = false;
+            edges.push((button, Edge::Release));
+        }
+
+        edges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `values` to the left trigger and returns the edges they made.
+    fn edges(triggers: &mut Triggers, values: &[f32], settings: &Settings) -> Vec<Edge> {
+        values
+            .iter()
+            .flat_map(|v| triggers.changed("lb", *v, settings))
+            .map(|(_, edge)| edge)
+            .collect()
+    }
+
+    #[test]
+    fn thresholds_with_hysteresis() {
+        let settings = Settings::default();
+        let mut triggers = Triggers::default();
+
+        assert_eq!(edges(&mut triggers, &[0.2, 0.49], &settings), []);
+        assert_eq!(edges(&mut triggers, &[0.5], &settings), [Edge::Press]);
+        // bouncing inside the band below actuation holds the press
+        assert_eq!(edges(&mut triggers, &[0.46, 0.5, 0.451], &settings), []);
+        assert_eq!(edges(&mut triggers, &[0.44], &settings), [Edge::Release]);
+        assert_eq!(edges(&mut triggers, &[0.46], &settings), []);
+        assert_eq!(edges(&mut triggers, &[0.5], &settings), [Edge::Press]);
+    }
+
+    #[test]
+    fn full_fires_once_until_the_trigger_comes_back() {
+        let settings = Settings::default();
+        let mut triggers = Triggers::default();
+
+        assert_eq!(
+            edges(&mut triggers, &[0.6, 0.96], &settings),
+            [Edge::Press, Edge::Full]
+        );
+        // inside the band below full it stays full
+        assert_eq!(edges(&mut triggers, &[0.91, 0.99, 0.905], &settings), []);
+        assert_eq!(edges(&mut triggers, &[0.89, 0.96], &settings), [Edge::Full]);
+        assert_eq!(edges(&mut triggers, &[0.], &settings), [Edge::Release]);
+    }
+
+    #[test]
+    fn a_jump_crosses_every_threshold_at_once() {
+        let settings = Settings::default();
+        let mut triggers = Triggers::default();
+
+        assert_eq!(
+            edges(&mut triggers, &[0.1, 0.97], &settings),
+            [Edge::Press, Edge::Full]
+        );
+        assert_eq!(edges(&mut triggers, &[0.], &settings), [Edge::Release]);
+    }
+
+    #[test]
+    fn a_released_trigger_lets_go_whatever_the_hysteresis() {
+        let settings = Settings {
+            trigger_hysteresis: 1.,
+            ..Settings::default()
+        };
+        let mut triggers = Triggers::default();
+
+        assert_eq!(edges(&mut triggers, &[0.1, 0.6], &settings), [Edge::Press]);
+        assert_eq!(edges(&mut triggers, &[0.01], &settings), []);
+        assert_eq!(edges(&mut triggers, &[0.], &settings), [Edge::Release]);
+    }
+
+    #[test]
+    fn digital_buttons_until_a_value_between() {
+        let settings = Settings::default();
+        let mut triggers = Triggers::default();
+
+        // values at the ends don't tell an analog trigger from a digital one
+        assert_eq!(edges(&mut triggers, &[1., 0.], &settings), []);
+        assert_eq!(triggers.digital("lb", true), [("lb", Edge::Press)]);
+        assert_eq!(triggers.digital("lb", false), [("lb", Edge::Release)]);
+
+        assert_eq!(edges(&mut triggers, &[0.3], &settings), []);
+        assert_eq!(triggers.digital("lb", true), []);
+    }
+}
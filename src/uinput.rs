@@ -0,0 +1,288 @@
+use rdev::{Button, Key};
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    mem,
+    os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+    slice, thread,
+    time::Duration,
+};
+
+use crate::sink::InputSink;
+
+// from linux/input-event-codes.h
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const SYN_REPORT: u16 = 0;
+const REL_X: u16 = 0x00;
+const REL_Y: u16 = 0x01;
+const REL_HWHEEL: u16 = 0x06;
+const REL_WHEEL: u16 = 0x08;
+const BTN_LEFT: u16 = 0x110;
+const BTN_RIGHT: u16 = 0x111;
+const BTN_MIDDLE: u16 = 0x112;
+const BUS_VIRTUAL: u16 = 0x06;
+
+/// `_IOW('U', nr, size)` from linux/uinput.h.
+const fn uinput_write(nr: u32, size: usize) -> u32 {
+    1 << 30 | (size as u32) << 16 | (b'U' as u32) << 8 | nr
+}
+
+const UI_DEV_CREATE: u32 = 0x5501;
+const UI_DEV_SETUP: u32 = uinput_write(3, mem::size_of::<libc::uinput_setup>());
+const UI_SET_EVBIT: u32 = uinput_write(100, mem::size_of::<libc::c_int>());
+const UI_SET_KEYBIT: u32 = uinput_write(101, mem::size_of::<libc::c_int>());
+const UI_SET_RELBIT: u32 = uinput_write(102, mem::size_of::<libc::c_int>());
+
+/// Event codes of the keys rdev names, `KEY_*` in linux/input-event-codes.h.
+const KEY_CODES: &[(Key, u16)] = &[
+    (Key::Escape, 1),
+    (Key::Num1, 2),
+    (Key::Num2, 3),
+    (Key::Num3, 4),
+    (Key::Num4, 5),
+    (Key::Num5, 6),
+    (Key::Num6, 7),
+    (Key::Num7, 8),
+    (Key::Num8, 9),
+    (Key::Num9, 10),
+    (Key::Num0, 11),
+    (Key::Minus, 12),
+    (Key::Equal, 13),
+    (Key::Backspace, 14),
+    (Key::Tab, 15),
+    (Key::KeyQ, 16),
+    (Key::KeyW, 17),
+    (Key::KeyE, 18),
+    (Key::KeyR, 19),
+    (Key::KeyT, 20),
+    (Key::KeyY, 21),
+    (Key::KeyU, 22),
+    (Key::KeyI, 23),
+    (Key::KeyO, 24),
+    (Key::KeyP, 25),
+    (Key::LeftBracket, 26),
+    (Key::RightBracket, 27),
+    (Key::Return, 28),
+    (Key::ControlLeft, 29),
+    (Key::KeyA, 30),
+    (Key::KeyS, 31),
+    (Key::KeyD, 32),
+    (Key::KeyF, 33),
+    (Key::KeyG, 34),
+    (Key::KeyH, 35),
+    (Key::KeyJ, 36),
+    (Key::KeyK, 37),
+    (Key::KeyL, 38),
+    (Key::SemiColon, 39),
+    (Key::Quote, 40),
+    (Key::BackQuote, 41),
+    (Key::ShiftLeft, 42),
+    (Key::BackSlash, 43),
+    (Key::KeyZ, 44),
+    (Key::KeyX, 45),
+    (Key::KeyC, 46),
+    (Key::KeyV, 47),
+    (Key::KeyB, 48),
+    (Key::KeyN, 49),
+    (Key::KeyM, 50),
+    (Key::Comma, 51),
+    (Key::Dot, 52),
+    (Key::Slash, 53),
+    (Key::ShiftRight, 54),
+    (Key::KpMultiply, 55),
+    (Key::Alt, 56),
+    (Key::Space, 57),
+    (Key::CapsLock, 58),
+    (Key::F1, 59),
+    (Key::F2, 60),
+    (Key::F3, 61),
+    (Key::F4, 62),
+    (Key::F5, 63),
+    (Key::F6, 64),
+    (Key::F7, 65),
+    (Key::F8, 66),
+    (Key::F9, 67),
+    (Key::F10, 68),
+    (Key::NumLock, 69),
+    (Key::ScrollLock, 70),
+    (Key::Kp7, 71),
+    (Key::Kp8, 72),
+    (Key::Kp9, 73),
+    (Key::KpMinus, 74),
+    (Key::Kp4, 75),
+    (Key::Kp5, 76),
+    (Key::Kp6, 77),
+    (Key::KpPlus, 78),
+    (Key::Kp1, 79),
+    (Key::Kp2, 80),
+    (Key::Kp3, 81),
+    (Key::Kp0, 82),
+    (Key::KpDelete, 83),
+    (Key::IntlBackslash, 86),
+    (Key::F11, 87),
+    (Key::F12, 88),
+    (Key::KpReturn, 96),
+    (Key::ControlRight, 97),
+    (Key::KpDivide, 98),
+    (Key::PrintScreen, 99),
+    (Key::AltGr, 100),
+    (Key::Home, 102),
+    (Key::UpArrow, 103),
+    (Key::PageUp, 104),
+    (Key::LeftArrow, 105),
+    (Key::RightArrow, 106),
+    (Key::End, 107),
+    (Key::DownArrow, 108),
+    (Key::PageDown, 109),
+    (Key::Insert, 110),
+    (Key::Delete, 111),
+    (Key::Pause, 119),
+    (Key::MetaLeft, 125),
+    (Key::MetaRight, 126),
+];
+
+/// The highest key code the device says it has, past the media and brightness keys.
+const MAX_KEY_CODE: u16 = 255;
+
+fn key_code(key: Key) -> Option<u16> {
+    match key {
+        // keys named by event code are X11 keycodes here, see `keys::raw_key`
+        Key::Unknown(code) => code.checked_sub(8).and_then(|c| u16::try_from(c).ok()),
+        key => KEY_CODES.iter().find(|(k, _)| *k == key).map(|(_, c)| *c),
+    }
+}
+
+fn button_code(button: Button) -> Option<u16> {
+    match button {
+        Button::Left => Some(BTN_LEFT),
+        Button::Right => Some(BTN_RIGHT),
+        Button::Middle => Some(BTN_MIDDLE),
+        Button::Unknown(_) => None,
+    }
+}
+
+fn ioctl(file: &File, request: u32, arg: libc::c_ulong) -> io::Result<()> {
+    // SAFETY: the requests used here take an int or a pointer to a live `uinput_setup`
+    match unsafe { libc::ioctl(file.as_raw_fd(), request as _, arg) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+/// A virtual keyboard and mouse made through `/dev/uinput`, which the kernel feeds to every
+/// session, Wayland included. It goes away when the file is closed.
+pub struct Uinput {
+    file: File,
+    /// the part of a pixel moves haven't sent yet
+    remainder: (f64, f64),
+}
+
+impl Uinput {
+    pub fn create() -> Result<Self, String> {
+        Self::open().map_err(|e| format!("couldn't set up /dev/uinput: {}", e))
+    }
+
+    fn open() -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open("/dev/uinput")?;
+
+        ioctl(&file, UI_SET_EVBIT, EV_KEY.into())?;
+        for code in 1..=MAX_KEY_CODE {
+            ioctl(&file, UI_SET_KEYBIT, code.into())?;
+        }
+        for code in [BTN_LEFT, BTN_RIGHT, BTN_MIDDLE] {
+            ioctl(&file, UI_SET_KEYBIT, code.into())?;
+        }
+        ioctl(&file, UI_SET_EVBIT, EV_REL.into())?;
+        for code in [REL_X, REL_Y, REL_HWHEEL, REL_WHEEL] {
+            ioctl(&file, UI_SET_RELBIT, code.into())?;
+        }
+
+        // SAFETY: all integers and a char array, zero is a valid value for each
+        let mut setup: libc::uinput_setup = unsafe { mem::zeroed() };
+        setup.id.bustype = BUS_VIRTUAL;
+        for (dst, src) in setup.name.iter_mut().zip(b"orchidbox") {
+            *dst = *src as libc::c_char;
+        }
+        ioctl(&file, UI_DEV_SETUP, &setup as *const _ as libc::c_ulong)?;
+        ioctl(&file, UI_DEV_CREATE, 0)?;
+
+        // the compositor has to pick up the new device before it listens to it
+        thread::sleep(Duration::from_millis(200));
+
+        Ok(Self {
+            file,
+            remainder: (0., 0.),
+        })
+    }
+
+    fn emit(&mut self, kind: u16, code: u16, value: i32) -> io::Result<()> {
+        // SAFETY: as above, and the kernel fills in the time
+        let mut event: libc::input_event = unsafe { mem::zeroed() };
+        event.type_ = kind;
+        event.code = code;
+        event.value = value;
+
+        // SAFETY: `event` is plain data and lives until the write is done
+        let bytes = unsafe {
+            slice::from_raw_parts(
+                &event as *const _ as *const u8,
+                mem::size_of::<libc::input_event>(),
+            )
+        };
+        self.file.write_all(bytes)
+    }
+
+    /// Sends motion along two axes, leaving out the ones that don't move.
+    fn relative(&mut self, x: i64, y: i64, x_axis: u16, y_axis: u16) -> Result<(), String> {
+        let clamp = |v: i64| v.clamp(i32::MIN.into(), i32::MAX.into()) as i32;
+        let events: Vec<_> = [(x_axis, x), (y_axis, y)]
+            .into_iter()
+            .filter(|(_, v)| *v != 0)
+            .map(|(axis, v)| (EV_REL, axis, clamp(v)))
+            .collect();
+        if events.is_empty() {
+            return Ok(());
+        }
+        self.report(&events)
+    }
+
+    /// Sends `events` as one report.
+    fn report(&mut self, events: &[(u16, u16, i32)]) -> Result<(), String> {
+        events
+            .iter()
+            .chain([&(EV_SYN, SYN_REPORT, 0)])
+            .try_for_each(|(kind, code, value)| self.emit(*kind, *code, *value))
+            .map_err(|e| format!("couldn't write to /dev/uinput: {}", e))
+    }
+}
+
+impl InputSink for Uinput {
+    fn key(&mut self, key: Key, down: bool) -> Result<(), String> {
+        let code = key_code(key).ok_or_else(|| format!("{:?} has no uinput key code", key))?;
+        self.report(&[(EV_KEY, code, down.into())])
+    }
+
+    fn button(&mut self, button: Button, down: bool) -> Result<(), String> {
+        let code =
+            button_code(button).ok_or_else(|| format!("{:?} has no uinput button", button))?;
+        self.report(&[(EV_KEY, code, down.into())])
+    }
+
+    fn move_by(&mut self, dx: f64, dy: f64) -> Result<(), String> {
+        let x = self.remainder.0 + dx;
+        let y = self.remainder.1 + dy;
+        self.remainder = (x.fract(), y.fract());
+
+        self.relative(x.trunc() as i64, y.trunc() as i64, REL_X, REL_Y)
+    }
+
+    fn wheel(&mut self, dx: i64, dy: i64) -> Result<(), String> {
+        self.relative(dx, dy, REL_HWHEEL, REL_WHEEL)
+    }
+}